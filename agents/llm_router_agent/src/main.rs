@@ -23,6 +23,7 @@ async fn main() {
     // 2. Process Request
     let payload = &request.payload.0;
     let prompt = payload.get("prompt").and_then(|v| v.as_str()).unwrap_or("");
+    let system = payload.get("system").and_then(|v| v.as_str()).unwrap_or("");
     let config = payload.get("config");
 
     let result = if let Some(config) = config {
@@ -30,7 +31,7 @@ async fn main() {
         let base_url = config.get("base_url").and_then(|v| v.as_str()).unwrap_or("https://openrouter.ai/api/v1");
         let model_name = config.get("model_name").and_then(|v| v.as_str()).unwrap_or("google/gemini-2.0-flash-exp:free");
 
-        match call_llm_provider(base_url, api_key, model_name, prompt).await {
+        match call_llm_provider(base_url, api_key, model_name, system, prompt).await {
             Ok(response_text) => ActionResult {
                 output_type: "text".to_string(),
                 data: response_text,
@@ -73,15 +74,19 @@ async fn main() {
     print!("{}", response_json);
 }
 
-async fn call_llm_provider(base_url: &str, api_key: &str, model: &str, prompt: &str) -> Result<String, String> {
+async fn call_llm_provider(base_url: &str, api_key: &str, model: &str, system: &str, prompt: &str) -> Result<String, String> {
     let client = Client::new();
     let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
 
+    let mut messages = Vec::new();
+    if !system.is_empty() {
+        messages.push(json!({"role": "system", "content": system}));
+    }
+    messages.push(json!({"role": "user", "content": prompt}));
+
     let body = json!({
         "model": model,
-        "messages": [
-            {"role": "user", "content": prompt}
-        ]
+        "messages": messages
     });
 
     let res = client.post(&url)
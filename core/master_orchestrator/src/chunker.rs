@@ -0,0 +1,242 @@
+use shared_types::ChunkingConfig;
+use std::ops::Range;
+
+// Splits long memory texts into chunks that are embedded separately, so a
+// long note isn't averaged into one vector. Markdown is cut at headings and
+// paragraph breaks, packing neighbouring pieces up to `max_chars`; other
+// text, and any piece still too long, goes through an overlapping sliding
+// window that prefers to break at whitespace. Chunks are byte ranges of the
+// original text, always on char boundaries.
+
+pub fn chunk(text: &str, config: &ChunkingConfig) -> Vec<Range<usize>> {
+    let max = config.max_chars.max(1);
+    let whole = 0..text.len();
+    if !config.enabled || text.len() <= max {
+        return vec![whole];
+    }
+
+    let units = if is_markdown(text) {
+        markdown_units(text, max, config.overlap_chars)
+    } else {
+        window(text, whole, max, config.overlap_chars).into_iter().map(|r| (r, false)).collect()
+    };
+    absorb_blank(text, pack(units, max))
+}
+
+// Whitespace-only chunks aren't worth embedding, but dropping them would
+// leave holes that reassemble() marks as deleted text. Each one is folded
+// into the chunk before it, or the one after it at the start of the text.
+fn absorb_blank(text: &str, chunks: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    let mut kept: Vec<Range<usize>> = Vec::new();
+    let mut leading: Option<usize> = None;
+    for chunk in chunks {
+        let blank = text[chunk.clone()].trim().is_empty();
+        match kept.last_mut() {
+            Some(last) if blank => last.end = last.end.max(chunk.end),
+            None if blank => leading = Some(leading.unwrap_or(chunk.start)),
+            _ => kept.push(leading.take().unwrap_or(chunk.start)..chunk.end),
+        }
+    }
+    kept
+}
+
+fn is_markdown(text: &str) -> bool {
+    text.lines().any(|line| heading_level(line).is_some())
+}
+
+// "## Title" -> Some(2)
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')) {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+// Sections that fit, else their paragraphs, else windows over those.
+// The flag marks units that open a section.
+fn markdown_units(text: &str, max: usize, overlap: usize) -> Vec<(Range<usize>, bool)> {
+    let mut units = Vec::new();
+    for section in split_before(text, 0..text.len(), |line| heading_level(line).is_some()) {
+        let first = units.len();
+        if section.len() <= max {
+            units.push((section, false));
+        } else {
+            for paragraph in paragraphs(text, section) {
+                if paragraph.len() <= max {
+                    units.push((paragraph, false));
+                } else {
+                    units.extend(window(text, paragraph, max, overlap).into_iter().map(|r| (r, false)));
+                }
+            }
+        }
+        units[first].1 = true;
+    }
+    units
+}
+
+// Splits `range` into pieces, each starting at a line `starts` accepts
+// (or at the beginning of the range).
+fn split_before(text: &str, range: Range<usize>, mut starts: impl FnMut(&str) -> bool) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut piece_start = range.start;
+    let mut line_start = range.start;
+    for line in text[range.clone()].split_inclusive('\n') {
+        if line_start > piece_start && starts(line) {
+            pieces.push(piece_start..line_start);
+            piece_start = line_start;
+        }
+        line_start += line.len();
+    }
+    if piece_start < range.end {
+        pieces.push(piece_start..range.end);
+    }
+    pieces
+}
+
+// Paragraphs end after a blank line; the blank line stays with the
+// paragraph before it.
+fn paragraphs(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut previous_blank = false;
+    split_before(text, range, |line| {
+        let blank = line.trim().is_empty();
+        let starts = previous_blank && !blank;
+        previous_blank = blank;
+        starts
+    })
+}
+
+// Overlapping windows of at most `max` bytes over `range`.
+fn window(text: &str, range: Range<usize>, max: usize, overlap: usize) -> Vec<Range<usize>> {
+    let overlap = overlap.min(max / 2);
+    let mut windows = Vec::new();
+    let mut start = range.start;
+    loop {
+        if range.end - start <= max {
+            windows.push(start..range.end);
+            return windows;
+        }
+        // Break after the last whitespace in the second half, if any
+        let limit = floor_char_boundary(text, start + max);
+        let mid = ceil_char_boundary(text, start + max / 2).min(limit);
+        let mut end = match text[mid..limit].char_indices().rev().find(|(_, c)| c.is_whitespace()) {
+            Some((i, c)) => mid + i + c.len_utf8(),
+            None => limit,
+        };
+        if end <= start {
+            end = ceil_char_boundary(text, start + 1);
+        }
+        windows.push(start..end);
+
+        // Start the overlap at a word boundary
+        let back = floor_char_boundary(text, end.saturating_sub(overlap).max(start));
+        let next = match text[back..end].char_indices().find(|(_, c)| c.is_whitespace()) {
+            Some((i, c)) => back + i + c.len_utf8(),
+            None => back,
+        };
+        start = if next > start && next < end { next } else { end };
+    }
+}
+
+// Merges consecutive units while they fit in `max`. A new section starts
+// a new chunk unless the current one is still small. Overlapping windows
+// are never merged, so their overlap is not duplicated within a chunk.
+fn pack(units: Vec<(Range<usize>, bool)>, max: usize) -> Vec<Range<usize>> {
+    let mut chunks: Vec<Range<usize>> = Vec::new();
+    for (unit, opens_section) in units {
+        match chunks.last_mut() {
+            Some(last)
+                if last.end == unit.start
+                    && unit.end - last.start <= max
+                    && (!opens_section || last.len() < max / 4) =>
+            {
+                last.end = unit.end
+            }
+            _ => chunks.push(unit),
+        }
+    }
+    chunks
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+// Rebuilds a document from its chunks, given as (start, text) sorted by
+// start. Overlaps are dropped; a gap left by a deleted chunk is marked.
+pub fn reassemble(chunks: &[(usize, String)]) -> String {
+    let mut out = String::new();
+    let mut covered: Option<usize> = None;
+    for (start, text) in chunks {
+        let end = start + text.len();
+        match covered {
+            Some(c) if *start < c => {
+                if end > c {
+                    out.push_str(&text[c - start..]);
+                }
+            }
+            Some(c) => {
+                if *start > c {
+                    out.push_str("\n[...]\n");
+                }
+                out.push_str(text);
+            }
+            None => out.push_str(text),
+        }
+        covered = Some(covered.map_or(end, |c| c.max(end)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_chars: usize, overlap_chars: usize) -> ChunkingConfig {
+        ChunkingConfig {
+            enabled: true,
+            max_chars,
+            overlap_chars,
+        }
+    }
+
+    fn round_trip(text: &str, config: &ChunkingConfig) -> String {
+        let chunks: Vec<(usize, String)> =
+            chunk(text, config).into_iter().map(|r| (r.start, text[r].to_string())).collect();
+        reassemble(&chunks)
+    }
+
+    #[test]
+    fn reassembles_text_with_whitespace_runs() {
+        let text = "a\n\n\n\n\n\n\n\n\n\n".repeat(200);
+        assert_eq!(round_trip(&text, &config(5, 2)), text);
+    }
+
+    #[test]
+    fn reassembles_leading_whitespace_and_markdown() {
+        let text = format!("{}# Title\n\n{}\n\n## Next\n\n{}   \n\n", " ".repeat(40), "word ".repeat(30), "more ".repeat(30));
+        for (max, overlap) in [(16, 4), (50, 10), (100, 0)] {
+            assert_eq!(round_trip(&text, &config(max, overlap)), text);
+        }
+    }
+
+    #[test]
+    fn never_returns_blank_chunks() {
+        let text = "a\n\n\n\n\n\n\n\n\n\n".repeat(200);
+        assert!(chunk(&text, &config(5, 2)).iter().all(|r| !text[r.clone()].trim().is_empty()));
+    }
+}
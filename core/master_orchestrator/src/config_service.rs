@@ -1,6 +1,5 @@
 use shared_types::AppConfig;
 use std::fs;
-use toml;
 use regex::Regex;
 use std::env;
 
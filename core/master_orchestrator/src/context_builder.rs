@@ -0,0 +1,411 @@
+use crate::entity_extractor::ExtractedEntity;
+use crate::goals::{relevance, GoalStatus};
+use crate::memory_service::{MemoryFilter, MemoryService, ScoreBreakdown};
+use async_trait::async_trait;
+use serde::Serialize;
+use shared_types::{AppConfig, ContextConfig};
+use std::collections::HashSet;
+
+// Context Builder: gathers candidates from every memory layer, ranks them,
+// removes duplicates and packs as many as fit into the model's token budget.
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextSource {
+    Goals,
+    KnowledgeGraph,
+    Semantic,
+    SessionHistory,
+}
+
+impl ContextSource {
+    fn heading(&self) -> &'static str {
+        match self {
+            ContextSource::Goals => "[Active Goals]",
+            ContextSource::KnowledgeGraph => "[Structured Memory]",
+            ContextSource::Semantic => "[Semantic Memory]",
+            ContextSource::SessionHistory => "[Session History]",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextCandidate {
+    pub source: ContextSource,
+    pub text: String,
+    pub score: f32,
+    // Retrieval ranking details, for semantic memories.
+    pub breakdown: Option<ScoreBreakdown>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Included,
+    Truncated,
+    Duplicate,
+    OverBudget,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ContextEntryReport {
+    pub source: ContextSource,
+    pub score: f32,
+    pub tokens: usize,
+    pub status: EntryStatus,
+    pub preview: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<ScoreBreakdown>,
+}
+
+// Written to `action_trace_log.context_report_json` alongside each trace.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ContextReport {
+    pub entities: Vec<String>,
+    pub providers: Vec<String>,
+    pub budget_tokens: usize,
+    pub used_tokens: usize,
+    pub entries: Vec<ContextEntryReport>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssembledContext {
+    pub text: String,
+    // Selected items per layer, for prompt templates that lay them out themselves.
+    pub goals: Vec<String>,
+    pub facts: Vec<String>,
+    pub memories: Vec<String>,
+    pub history: Vec<String>,
+    pub report: ContextReport,
+}
+
+// Rough chars-per-token ratios by model family. Good enough for budgeting;
+// exact counts would require each provider's tokenizer.
+pub fn chars_per_token(model: &str, config: &ContextConfig) -> f32 {
+    let model_lc = model.to_lowercase();
+    if let Some((_, ratio)) = config
+        .chars_per_token
+        .iter()
+        .filter(|(prefix, _)| model_lc.starts_with(&prefix.to_lowercase()))
+        .max_by_key(|(prefix, _)| prefix.len())
+    {
+        return *ratio;
+    }
+
+    if model_lc.contains("llama") || model_lc.contains("mistral") || model_lc.contains("qwen") {
+        3.5
+    } else {
+        4.0
+    }
+}
+
+pub fn estimate_tokens(text: &str, model: &str, config: &ContextConfig) -> usize {
+    let ratio = chars_per_token(model, config).max(0.1);
+    (text.chars().count() as f32 / ratio).ceil() as usize
+}
+
+pub fn budget_for(provider: Option<&str>, model: &str, config: &ContextConfig) -> usize {
+    if let Some(provider) = provider {
+        if let Some(budget) = config.budgets.get(&format!("{}:{}", provider, model)) {
+            return *budget;
+        }
+        if let Some(budget) = config.budgets.get(provider) {
+            return *budget;
+        }
+    }
+    config.default_budget_tokens
+}
+
+// Everything a provider may need to produce candidates for one request.
+pub struct ContextQuery<'a> {
+    pub memory_service: &'a MemoryService,
+    pub user_message: &'a str,
+    pub entities: &'a [ExtractedEntity],
+    pub session_id: Option<&'a str>,
+    pub app_config: &'a AppConfig,
+}
+
+#[async_trait]
+pub trait ContextProvider: Send + Sync {
+    fn name(&self) -> &str;
+    async fn candidates(&self, query: &ContextQuery<'_>) -> Vec<ContextCandidate>;
+}
+
+// Active goals, with their latest note; goals related to the message rank
+// higher but every active goal scores well enough to usually be included.
+pub struct GoalsProvider;
+
+#[async_trait]
+impl ContextProvider for GoalsProvider {
+    fn name(&self) -> &str {
+        "goals"
+    }
+
+    async fn candidates(&self, query: &ContextQuery<'_>) -> Vec<ContextCandidate> {
+        let config = &query.app_config.context;
+        let goals = query.memory_service.list_goals(Some(GoalStatus::Active)).await.unwrap_or_default();
+
+        let embedder = query.memory_service.embedder();
+        let scores = relevance(&goals, query.user_message, embedder.as_ref()).await.unwrap_or_else(|e| {
+            eprintln!("Goal relevance failed: {}", e);
+            vec![0.0; goals.len()]
+        });
+
+        let mut candidates = Vec::new();
+        for (goal, relevance) in goals.iter().zip(scores) {
+            let latest = query.memory_service.get_goal_notes(goal.id, 1).await.unwrap_or_default();
+            let text = match latest.first() {
+                Some(note) => format!("{} - latest: {}", goal.summary(), note.text),
+                None => goal.summary(),
+            };
+            candidates.push(ContextCandidate {
+                source: ContextSource::Goals,
+                text,
+                score: config.goals_weight * (0.6 + 0.4 * relevance),
+                breakdown: None,
+            });
+        }
+        candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.truncate(config.max_goals);
+        candidates
+    }
+}
+
+// Facts about the entities the message mentions.
+pub struct KnowledgeGraphProvider;
+
+#[async_trait]
+impl ContextProvider for KnowledgeGraphProvider {
+    fn name(&self) -> &str {
+        "knowledge_graph"
+    }
+
+    async fn candidates(&self, query: &ContextQuery<'_>) -> Vec<ContextCandidate> {
+        let config = &query.app_config.context;
+        let entity_scores: Vec<(String, f32)> = query.entities.iter().map(|e| (e.name.clone(), e.score)).collect();
+        if entity_scores.is_empty() {
+            return Vec::new();
+        }
+
+        let facts = query
+            .memory_service
+            .retrieve_structured_context(
+                &entity_scores,
+                config.max_kg_facts,
+                query.app_config.entities.include_neighbours,
+                !query.app_config.facts.require_review,
+            )
+            .await
+            .unwrap_or_default();
+
+        facts
+            .into_iter()
+            .map(|fact| ContextCandidate {
+                source: ContextSource::KnowledgeGraph,
+                text: if fact.hops > 0 { format!("{} (related)", fact) } else { fact.to_string() },
+                score: config.kg_weight * fact.score,
+                breakdown: None,
+            })
+            .collect()
+    }
+}
+
+pub struct SemanticMemoryProvider;
+
+#[async_trait]
+impl ContextProvider for SemanticMemoryProvider {
+    fn name(&self) -> &str {
+        "semantic"
+    }
+
+    async fn candidates(&self, query: &ContextQuery<'_>) -> Vec<ContextCandidate> {
+        let config = &query.app_config.context;
+        query
+            .memory_service
+            .retrieve_semantic_context(
+                query.user_message,
+                config.max_semantic_memories,
+                &query.app_config.retrieval,
+                &MemoryFilter::default(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Semantic retrieval failed: {}", e);
+                Vec::new()
+            })
+            .into_iter()
+            .map(|hit| ContextCandidate {
+                source: ContextSource::Semantic,
+                text: hit.memory.text,
+                score: config.semantic_weight * hit.score,
+                breakdown: Some(hit.breakdown),
+            })
+            .collect()
+    }
+}
+
+// Recent messages of the session; more recent messages rank higher.
+pub struct SessionHistoryProvider;
+
+#[async_trait]
+impl ContextProvider for SessionHistoryProvider {
+    fn name(&self) -> &str {
+        "session_history"
+    }
+
+    async fn candidates(&self, query: &ContextQuery<'_>) -> Vec<ContextCandidate> {
+        let config = &query.app_config.context;
+        let Some(session_id) = query.session_id else {
+            return Vec::new();
+        };
+        let history = query
+            .memory_service
+            .get_session_history(session_id, config.max_history_messages)
+            .await
+            .unwrap_or_default();
+
+        let len = history.len();
+        history
+            .into_iter()
+            .enumerate()
+            .map(|(i, msg)| ContextCandidate {
+                source: ContextSource::SessionHistory,
+                text: format!("{}: {}", msg.role, msg.content),
+                score: config.history_weight * 0.9f32.powi((len - 1 - i) as i32),
+                breakdown: None,
+            })
+            .collect()
+    }
+}
+
+pub fn build_provider(name: &str) -> Result<Box<dyn ContextProvider>, String> {
+    match name {
+        "goals" => Ok(Box::new(GoalsProvider)),
+        "knowledge_graph" => Ok(Box::new(KnowledgeGraphProvider)),
+        "semantic" => Ok(Box::new(SemanticMemoryProvider)),
+        "session_history" => Ok(Box::new(SessionHistoryProvider)),
+        other => Err(format!("Unknown context provider '{}'", other)),
+    }
+}
+
+pub async fn build_context(
+    query: &ContextQuery<'_>,
+    provider: Option<&str>,
+    model: &str,
+) -> AssembledContext {
+    let config = &query.app_config.context;
+    let mut candidates = Vec::new();
+    let mut used_providers = Vec::new();
+
+    for name in &config.providers {
+        match build_provider(name) {
+            Ok(p) => {
+                candidates.extend(p.candidates(query).await);
+                used_providers.push(p.name().to_string());
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    let budget = budget_for(provider, model, config);
+    let mut assembled = assemble(candidates, budget, model, config);
+    assembled.report.entities = query.entities.iter().map(|e| e.name.clone()).collect();
+    assembled.report.providers = used_providers;
+    assembled
+}
+
+pub fn assemble(mut candidates: Vec<ContextCandidate>, budget: usize, model: &str, config: &ContextConfig) -> AssembledContext {
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut seen = HashSet::new();
+    let mut report = ContextReport {
+        budget_tokens: budget,
+        ..Default::default()
+    };
+    let mut selected: Vec<ContextCandidate> = Vec::new();
+
+    for candidate in candidates {
+        let tokens = estimate_tokens(&candidate.text, model, config);
+        let mut entry = ContextEntryReport {
+            source: candidate.source,
+            score: candidate.score,
+            tokens,
+            status: EntryStatus::Included,
+            preview: preview(&candidate.text),
+            breakdown: candidate.breakdown.clone(),
+        };
+
+        if !seen.insert(normalize(&candidate.text)) {
+            entry.status = EntryStatus::Duplicate;
+            report.entries.push(entry);
+            continue;
+        }
+
+        let remaining = budget.saturating_sub(report.used_tokens);
+        if tokens <= remaining {
+            report.used_tokens += tokens;
+            selected.push(candidate);
+        } else if remaining >= 32 {
+            // Only worth truncating if a meaningful slice still fits.
+            let max_chars = (remaining as f32 * chars_per_token(model, config)) as usize;
+            let truncated: String = candidate.text.chars().take(max_chars.saturating_sub(3)).collect();
+            let text = format!("{}...", truncated);
+            let used = estimate_tokens(&text, model, config).min(remaining);
+            report.used_tokens += used;
+            entry.tokens = used;
+            entry.status = EntryStatus::Truncated;
+            selected.push(ContextCandidate { text, ..candidate });
+        } else {
+            entry.status = EntryStatus::OverBudget;
+        }
+        report.entries.push(entry);
+    }
+
+    AssembledContext {
+        text: render(&selected),
+        goals: section(&selected, ContextSource::Goals),
+        facts: section(&selected, ContextSource::KnowledgeGraph),
+        memories: section(&selected, ContextSource::Semantic),
+        history: section(&selected, ContextSource::SessionHistory),
+        report,
+    }
+}
+
+fn section(selected: &[ContextCandidate], source: ContextSource) -> Vec<String> {
+    let mut items: Vec<String> = selected.iter().filter(|c| c.source == source).map(|c| c.text.clone()).collect();
+    if source == ContextSource::SessionHistory {
+        // History scores grow with recency; restore chronological order.
+        items.reverse();
+    }
+    items
+}
+
+// Sections are emitted in a fixed order regardless of ranking so the prompt
+// layout stays stable between requests.
+fn render(selected: &[ContextCandidate]) -> String {
+    let mut out = String::new();
+    for source in [ContextSource::Goals, ContextSource::KnowledgeGraph, ContextSource::Semantic, ContextSource::SessionHistory] {
+        let items = section(selected, source);
+        if items.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n{}:\n", source.heading()));
+        for item in items {
+            out.push_str(&format!("- {}\n", item));
+        }
+    }
+    out
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn preview(text: &str) -> String {
+    const MAX: usize = 80;
+    if text.chars().count() > MAX {
+        format!("{}...", text.chars().take(MAX).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
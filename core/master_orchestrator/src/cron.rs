@@ -0,0 +1,153 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+// Minimal five-field cron expressions: "minute hour day-of-month month day-of-week".
+// Supports `*`, lists, ranges, steps, month/day names and the @hourly, @daily,
+// @weekly, @monthly and @yearly shortcuts. Evaluated in local time.
+
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+const MONTH_NAMES: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// How far ahead `next_after` searches before giving up (e.g. "0 0 31 2 *").
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+fn parse_value(token: &str, min: u32, names: &[&str]) -> Result<u32, String> {
+    if let Some(pos) = names.iter().position(|n| n.eq_ignore_ascii_case(token)) {
+        return Ok(pos as u32 + min);
+    }
+    token.parse::<u32>().map_err(|_| format!("Invalid cron value '{}'", token))
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<(Vec<bool>, bool), String> {
+    let mut allowed = vec![false; max as usize + 1];
+    let restricted = field != "*";
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().map_err(|_| format!("Invalid cron step '{}'", step))?;
+                if step == 0 {
+                    return Err("Cron step must be positive".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, names)?, parse_value(b, min, names)?)
+        } else {
+            let v = parse_value(range, min, names)?;
+            // "5/15" means "from 5 every 15 until the end of the range".
+            (v, if step > 1 { max } else { v })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!("Cron field '{}' out of range {}-{}", field, min, max));
+        }
+
+        let mut v = start;
+        while v <= end {
+            allowed[v as usize] = true;
+            v += step;
+        }
+    }
+
+    Ok((allowed, restricted))
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("Cron expression must have 5 fields, got {}", fields.len()));
+        }
+
+        let (minutes, _) = parse_field(fields[0], 0, 59, &[])?;
+        let (hours, _) = parse_field(fields[1], 0, 23, &[])?;
+        let (days_of_month, dom_restricted) = parse_field(fields[2], 1, 31, &[])?;
+        let (months, _) = parse_field(fields[3], 1, 12, MONTH_NAMES)?;
+        let (mut days_of_week, dow_restricted) = parse_field(fields[4], 0, 7, DAY_NAMES)?;
+        // 7 is an alias for Sunday.
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+
+        Ok(Self {
+            minutes,
+            hours,
+            days_of_month,
+            months,
+            days_of_week,
+            dom_restricted,
+            dow_restricted,
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if !self.months[date.month() as usize] {
+            return false;
+        }
+        let dom = self.days_of_month[date.day() as usize];
+        let dow = self.days_of_week[date.weekday().num_days_from_sunday() as usize];
+        // Classic cron: if both fields are restricted, either may match.
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    // First matching minute strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date();
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_day(date) {
+                for hour in 0..24u32 {
+                    if !self.hours[hour as usize] {
+                        continue;
+                    }
+                    for minute in 0..60u32 {
+                        if !self.minutes[minute as usize] {
+                            continue;
+                        }
+                        let candidate = NaiveDateTime::new(date, chrono::NaiveTime::from_hms_opt(hour, minute, 0)?);
+                        if candidate < start {
+                            continue;
+                        }
+                        // Skips times that fall into a DST gap.
+                        if let Some(local) = Local.from_local_datetime(&candidate).earliest() {
+                            return Some(local);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
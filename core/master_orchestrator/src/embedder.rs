@@ -0,0 +1,375 @@
+use crate::embedding::{self, fnv1a64, EmbedderId};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared_types::{AppConfig, EmbeddingConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Embedders turn memory texts into vectors. The one in use is chosen by
+// [embedding] in config; every vector it produces is tagged with its
+// EmbedderId, so switching embedders never mixes incompatible vectors.
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    fn id(&self) -> EmbedderId;
+
+    // One vector per text, in order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String>;
+
+    // Called with the text of every newly stored memory (not with merged
+    // duplicates), for embedders that keep corpus statistics.
+    fn observe(&self, _text: &str) {}
+
+    // Embedders that keep corpus statistics embed under a frozen snapshot
+    // of them, numbered by the id's version. `corpus_state` is the next
+    // snapshot (everything observed so far), `with_state` the same embedder
+    // frozen at a snapshot, and `stale` whether the observed statistics
+    // have moved far enough from the frozen ones to reindex. Stateless
+    // embedders have no snapshots.
+    fn corpus_state(&self) -> Option<String> {
+        None
+    }
+
+    fn with_state(&self, _state: &str) -> Result<Arc<dyn Embedder>, String> {
+        Err(format!("{} keeps no corpus statistics", self.id()))
+    }
+
+    fn stale(&self) -> bool {
+        false
+    }
+}
+
+pub async fn embed_one(embedder: &dyn Embedder, text: &str) -> Result<Vec<f32>, String> {
+    embedder
+        .embed(&[text.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| "Embedder returned no vector".to_string())
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        for x in &mut vector {
+            *x /= magnitude;
+        }
+    }
+    vector
+}
+
+// --- Hash (built-in) ---
+
+pub struct HashEmbedder;
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn id(&self) -> EmbedderId {
+        embedding::simple_hash_id()
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Ok(texts.iter().map(|t| embedding::simple_hash_embedding(t)).collect())
+    }
+}
+
+// --- TF-IDF over hashed n-grams ---
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct DocumentFrequencies {
+    // Snapshot number, the id's version; 1 is the empty snapshot.
+    version: u32,
+    documents: u64,
+    // Documents containing each bucket.
+    buckets: Vec<u32>,
+}
+
+// The frozen snapshot is refreshed by a reindex once the corpus has grown
+// this many times over.
+const TFIDF_REFRESH_GROWTH: u64 = 2;
+// ... and has at least this many documents.
+const TFIDF_REFRESH_MIN_DOCUMENTS: u64 = 16;
+
+// Word n-grams (1..=word_ngrams) and character n-grams of each word are
+// hashed into `dim` signed buckets and weighted by (1 + ln tf) * idf.
+// Document frequencies are counted per bucket over every stored memory,
+// but vectors are weighted by a frozen snapshot of them, so every vector
+// of one id version shares the same weights. A reindex freezes the
+// counts observed since.
+pub struct TfIdfEmbedder {
+    dim: usize,
+    word_ngrams: usize,
+    char_ngrams: usize,
+    frozen: DocumentFrequencies,
+    // Shared by every snapshot of this embedder
+    observed: Arc<Mutex<DocumentFrequencies>>,
+}
+
+impl TfIdfEmbedder {
+    pub fn new(dim: usize, word_ngrams: usize, char_ngrams: usize) -> Self {
+        let empty = DocumentFrequencies {
+            version: 1,
+            documents: 0,
+            buckets: vec![0; dim],
+        };
+        Self {
+            dim,
+            word_ngrams: word_ngrams.max(1),
+            char_ngrams,
+            frozen: empty.clone(),
+            observed: Arc::new(Mutex::new(empty)),
+        }
+    }
+    fn features(&self, text: &str) -> HashMap<String, u32> {
+        let lower = text.to_lowercase();
+        let words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+        let mut counts = HashMap::new();
+
+        for n in 1..=self.word_ngrams {
+            for window in words.windows(n) {
+                *counts.entry(format!("w:{}", window.join(" "))).or_insert(0) += 1;
+            }
+        }
+        if self.char_ngrams > 0 {
+            for word in &words {
+                let padded: Vec<char> = format!("<{}>", word).chars().collect();
+                for window in padded.windows(self.char_ngrams) {
+                    *counts.entry(format!("c:{}", window.iter().collect::<String>())).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    // Bucket and sign of a feature.
+    fn bucket(&self, feature: &str) -> (usize, f32) {
+        let hash = fnv1a64(feature.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        ((hash % self.dim as u64) as usize, sign)
+    }
+
+    fn vector(&self, text: &str) -> Vec<f32> {
+        let frequencies = &self.frozen;
+        let mut vector = vec![0.0; self.dim];
+        for (feature, tf) in self.features(text) {
+            let (bucket, sign) = self.bucket(&feature);
+            let idf = ((1 + frequencies.documents) as f32 / (1 + frequencies.buckets[bucket]) as f32).ln() + 1.0;
+            vector[bucket] += sign * (1.0 + (tf as f32).ln()) * idf;
+        }
+        normalize(vector)
+    }
+}
+
+#[async_trait]
+impl Embedder for TfIdfEmbedder {
+    fn id(&self) -> EmbedderId {
+        EmbedderId {
+            name: format!("tfidf-w{}c{}", self.word_ngrams, self.char_ngrams),
+            version: self.frozen.version,
+            dim: self.dim,
+        }
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Ok(texts.iter().map(|t| self.vector(t)).collect())
+    }
+
+    fn observe(&self, text: &str) {
+        let buckets: HashSet<usize> = self.features(text).keys().map(|f| self.bucket(f).0).collect();
+        let mut frequencies = self.observed.lock().unwrap();
+        frequencies.documents += 1;
+        for bucket in buckets {
+            frequencies.buckets[bucket] += 1;
+        }
+    }
+
+    fn corpus_state(&self) -> Option<String> {
+        let mut next = self.observed.lock().unwrap().clone();
+        next.version = self.frozen.version + 1;
+        serde_json::to_string(&next).ok()
+    }
+
+    fn with_state(&self, state: &str) -> Result<Arc<dyn Embedder>, String> {
+        let frozen: DocumentFrequencies = serde_json::from_str(state).map_err(|e| e.to_string())?;
+        if frozen.buckets.len() != self.dim {
+            return Err(format!("TF-IDF snapshot has {} buckets, expected {}", frozen.buckets.len(), self.dim));
+        }
+        Ok(Arc::new(Self {
+            dim: self.dim,
+            word_ngrams: self.word_ngrams,
+            char_ngrams: self.char_ngrams,
+            frozen,
+            observed: self.observed.clone(),
+        }))
+    }
+
+    fn stale(&self) -> bool {
+        let observed = self.observed.lock().unwrap().documents;
+        observed >= TFIDF_REFRESH_MIN_DOCUMENTS && observed >= TFIDF_REFRESH_GROWTH * self.frozen.documents
+    }
+}
+
+// --- HTTP (OpenAI-compatible or Ollama) ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HttpApi {
+    // POST {base}/embeddings {"model", "input": [...]} -> {"data": [{"embedding"}]}
+    OpenAi,
+    // POST {base}/api/embed {"model", "input": [...]} -> {"embeddings": [...]}
+    Ollama,
+}
+
+pub struct HttpEmbedder {
+    client: reqwest::Client,
+    api: HttpApi,
+    url: String,
+    api_key: Option<String>,
+    provider: String,
+    model: String,
+    dim: usize,
+    batch_size: usize,
+}
+
+impl HttpEmbedder {
+    // Probes the endpoint for the vector size unless `dim` is configured.
+    pub async fn new(config: &EmbeddingConfig, app_config: &AppConfig) -> Result<Self, String> {
+        let provider = config
+            .provider
+            .clone()
+            .unwrap_or_else(|| app_config.llm.default_provider.clone());
+        let provider_config = app_config
+            .llm
+            .provider(&provider)
+            .ok_or_else(|| format!("Embedding provider '{}' is not configured under [llm]", provider))?;
+        let api = match config.api.as_str() {
+            "openai" => HttpApi::OpenAi,
+            "ollama" => HttpApi::Ollama,
+            other => return Err(format!("Unknown embedding api '{}' (expected 'openai' or 'ollama')", other)),
+        };
+        let base = match (&provider_config.base_url, api) {
+            (Some(url), _) => url.trim_end_matches('/').to_string(),
+            (None, HttpApi::OpenAi) => "https://api.openai.com/v1".to_string(),
+            (None, HttpApi::Ollama) => "http://localhost:11434".to_string(),
+        };
+        let url = match api {
+            HttpApi::OpenAi => format!("{}/embeddings", base),
+            HttpApi::Ollama => format!("{}/api/embed", base),
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut embedder = Self {
+            client,
+            api,
+            url,
+            api_key: provider_config.api_key.clone(),
+            provider,
+            model: config.model.clone().unwrap_or_else(|| provider_config.model_name.clone()),
+            dim: config.dim.unwrap_or(0),
+            batch_size: config.batch_size.max(1),
+        };
+        if embedder.dim == 0 {
+            let probe = embedder.request(&["dimension probe".to_string()]).await?;
+            embedder.dim = probe.first().map(|v| v.len()).unwrap_or(0);
+            if embedder.dim == 0 {
+                return Err("Embedding endpoint returned an empty vector".to_string());
+            }
+        }
+        Ok(embedder)
+    }
+
+    async fn request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let mut request = self.client.post(&self.url).json(&json!({ "model": self.model, "input": texts }));
+        if let Some(key) = self.api_key.as_ref().filter(|k| !k.is_empty()) {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await.map_err(|e| format!("Embedding request failed: {}", e))?;
+        let status = response.status();
+        let body: Value = response.json().await.map_err(|e| format!("Embedding response unreadable: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Embedding endpoint returned {}: {}", status, body));
+        }
+
+        let vectors: Vec<&Value> = match self.api {
+            HttpApi::OpenAi => {
+                let mut data: Vec<&Value> = body["data"].as_array().ok_or("Embedding response has no 'data'")?.iter().collect();
+                data.sort_by_key(|d| d["index"].as_u64().unwrap_or(0));
+                data.into_iter().map(|d| &d["embedding"]).collect()
+            }
+            HttpApi::Ollama => body["embeddings"].as_array().ok_or("Embedding response has no 'embeddings'")?.iter().collect(),
+        };
+        if vectors.len() != texts.len() {
+            return Err(format!("Embedding endpoint returned {} vectors for {} texts", vectors.len(), texts.len()));
+        }
+        vectors
+            .into_iter()
+            .map(|v| {
+                let values = v.as_array().ok_or("Embedding is not an array")?;
+                Ok(normalize(values.iter().map(|x| x.as_f64().unwrap_or(0.0) as f32).collect()))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Embedder for HttpEmbedder {
+    fn id(&self) -> EmbedderId {
+        EmbedderId {
+            name: format!("http-{}-{}", self.provider, self.model),
+            version: 1,
+            dim: self.dim,
+        }
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            for vector in self.request(batch).await? {
+                if vector.len() != self.dim {
+                    return Err(format!("Embedding has {} dims, expected {}", vector.len(), self.dim));
+                }
+                vectors.push(vector);
+            }
+        }
+        Ok(vectors)
+    }
+}
+
+pub async fn build_embedder(app_config: &AppConfig) -> Result<Arc<dyn Embedder>, String> {
+    let config = &app_config.embedding;
+    match config.embedder.as_str() {
+        "hash" => Ok(Arc::new(HashEmbedder)),
+        "tfidf" => {
+            let dim = config.dim.unwrap_or(1024);
+            if dim == 0 {
+                return Err("[embedding] dim must be at least 1 for the tfidf embedder".to_string());
+            }
+            Ok(Arc::new(TfIdfEmbedder::new(dim, config.word_ngrams, config.char_ngrams)))
+        }
+        "http" => Ok(Arc::new(HttpEmbedder::new(config, app_config).await?)),
+        other => Err(format!("Unknown embedder '{}' (expected 'hash', 'tfidf' or 'http')", other)),
+    }
+}
+
+// HTTP embedders get a few tries, for an endpoint still starting up. There
+// is no fallback: stored vectors are only searchable with the embedder
+// that produced them.
+const HTTP_SETUP_ATTEMPTS: u32 = 5;
+const HTTP_SETUP_RETRY: Duration = Duration::from_secs(3);
+
+pub async fn build_embedder_with_retry(app_config: &AppConfig) -> Result<Arc<dyn Embedder>, String> {
+    let mut attempt = 1;
+    loop {
+        match build_embedder(app_config).await {
+            Err(e) if app_config.embedding.embedder == "http" && attempt < HTTP_SETUP_ATTEMPTS => {
+                eprintln!("Embedder setup failed (attempt {}/{}): {}", attempt, HTTP_SETUP_ATTEMPTS, e);
+                tokio::time::sleep(HTTP_SETUP_RETRY).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Embedding format: the stable hash embedder and the encoding of stored
+// vectors. Every stored vector carries a header naming the embedder,
+// its version and dimension, so vectors from different embedders are never
+// compared with each other.
+//
+// Stored layout (little-endian):
+//   b"GAIV" | format: u8 | name_len: u8 | name | version: u32 | dim: u32 | dim x f32
+
+const MAGIC: &[u8; 4] = b"GAIV";
+const FORMAT_VERSION: u8 = 1;
+
+// Bag-of-words hash embedder. Version 1 hashed with std's DefaultHasher,
+// which is not stable across Rust releases; version 2 uses FNV-1a.
+pub const SIMPLE_HASH_NAME: &str = "simple-hash";
+pub const SIMPLE_HASH_VERSION: u32 = 2;
+pub const SIMPLE_HASH_DIM: usize = 128;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EmbedderId {
+    pub name: String,
+    pub version: u32,
+    pub dim: usize,
+}
+
+impl fmt::Display for EmbedderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@v{}/{}", self.name, self.version, self.dim)
+    }
+}
+
+pub fn simple_hash_id() -> EmbedderId {
+    EmbedderId {
+        name: SIMPLE_HASH_NAME.to_string(),
+        version: SIMPLE_HASH_VERSION,
+        dim: SIMPLE_HASH_DIM,
+    }
+}
+
+// 64-bit FNV-1a (offset basis 0xcbf29ce484222325, prime 0x100000001b3).
+// Fixed by specification, so its output never changes between builds.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Each whitespace-separated word adds 1.0 to bucket fnv1a64(word) % dim;
+// the result is L2-normalized.
+pub fn simple_hash_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; SIMPLE_HASH_DIM];
+    for word in text.split_whitespace() {
+        let index = (fnv1a64(word.as_bytes()) % SIMPLE_HASH_DIM as u64) as usize;
+        vector[index] += 1.0;
+    }
+
+    let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        for x in &mut vector {
+            *x /= magnitude;
+        }
+    }
+    vector
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredVector {
+    pub embedder: EmbedderId,
+    pub values: Vec<f32>,
+}
+
+pub fn encode(embedder: &EmbedderId, values: &[f32]) -> Vec<u8> {
+    let name = embedder.name.as_bytes();
+    let mut bytes = Vec::with_capacity(14 + name.len() + values.len() * 4);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.push(name.len() as u8);
+    bytes.extend_from_slice(name);
+    bytes.extend_from_slice(&embedder.version.to_le_bytes());
+    bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn has_header(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<StoredVector, String> {
+    if !has_header(bytes) {
+        return Err("vector has no embedder header".to_string());
+    }
+    let format = *bytes.get(4).ok_or("truncated vector header")?;
+    if format != FORMAT_VERSION {
+        return Err(format!("unsupported vector format {}", format));
+    }
+    let name_len = *bytes.get(5).ok_or("truncated vector header")? as usize;
+    let name_end = 6 + name_len;
+    let header_end = name_end + 8;
+    if bytes.len() < header_end {
+        return Err("truncated vector header".to_string());
+    }
+    let name = String::from_utf8(bytes[6..name_end].to_vec()).map_err(|e| e.to_string())?;
+    let version = u32::from_le_bytes(bytes[name_end..name_end + 4].try_into().unwrap());
+    let dim = u32::from_le_bytes(bytes[name_end + 4..header_end].try_into().unwrap()) as usize;
+
+    let body = &bytes[header_end..];
+    if body.len() != dim * 4 {
+        return Err(format!("vector has {} bytes, header says {} dims", body.len(), dim));
+    }
+    Ok(StoredVector {
+        embedder: EmbedderId { name, version, dim },
+        values: body
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect(),
+    })
+}
@@ -0,0 +1,109 @@
+use crate::executor::{call_llm_agent, extract_json_span};
+use crate::memory_service::MemoryService;
+use shared_types::AppConfig;
+use std::collections::HashMap;
+
+// Entity Extraction: finds the knowledge-graph nodes a message talks about,
+// so the planner can query the graph with entities instead of whole sentences.
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "all", "am", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by",
+    "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he", "her", "him",
+    "his", "how", "i", "if", "in", "into", "is", "it", "its", "me", "my", "of", "on", "or", "our",
+    "please", "she", "so", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "to", "us", "was", "we", "were", "what", "when", "where", "which", "who", "why",
+    "will", "with", "would", "you", "your",
+];
+
+#[derive(Debug, Clone)]
+pub struct ExtractedEntity {
+    pub name: String,
+    pub score: f32,
+}
+
+pub fn is_stopword(word: &str) -> bool {
+    STOPWORDS.contains(&word)
+}
+
+// Lowercased words with surrounding punctuation stripped.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_' && c != '.'))
+        .map(|w| w.trim_end_matches('.').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+// Matches message n-grams against known graph nodes. Longer matches score
+// higher, and n-grams made only of stopwords are ignored.
+pub fn match_known_entities(message: &str, known: &[String], max_ngram: usize) -> Vec<ExtractedEntity> {
+    let index: HashMap<String, &String> = known
+        .iter()
+        .map(|name| (tokenize(name).join(" "), name))
+        .filter(|(key, _)| !key.is_empty())
+        .collect();
+
+    let words = tokenize(message);
+    let max_ngram = max_ngram.max(1);
+    let mut found: HashMap<String, f32> = HashMap::new();
+
+    for n in (1..=max_ngram.min(words.len())).rev() {
+        for window in words.windows(n) {
+            if window.iter().all(|w| is_stopword(w)) {
+                continue;
+            }
+            if let Some(name) = index.get(&window.join(" ")) {
+                let score = n as f32 / max_ngram as f32;
+                let entry = found.entry((*name).clone()).or_insert(0.0);
+                *entry = entry.max(score);
+            }
+        }
+    }
+
+    let mut entities: Vec<ExtractedEntity> = found
+        .into_iter()
+        .map(|(name, score)| ExtractedEntity { name, score: 0.5 + 0.5 * score })
+        .collect();
+    entities.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    entities
+}
+
+// Asks the LLM router for entity names. Any failure yields an empty list so
+// the rule-based matches still stand on their own.
+fn extract_with_llm(message: &str, app_config: &AppConfig) -> Vec<String> {
+    let prompt = format!(
+        "Extract the named entities (people, projects, servers, files, tools, places) mentioned in the message below. \
+         Respond with only a JSON array of strings.\n\nMessage: {}",
+        message
+    );
+
+    call_llm_agent(app_config, "extract_entities", &prompt)
+        .ok()
+        .and_then(|data| extract_json_span(&data, '[', ']').and_then(|json| serde_json::from_str(json).ok()))
+        .unwrap_or_default()
+}
+
+pub async fn extract_entities(
+    memory_service: &MemoryService,
+    message: &str,
+    app_config: &AppConfig,
+) -> Vec<ExtractedEntity> {
+    let known = memory_service.get_kg_entities(!app_config.facts.require_review).await.unwrap_or_default();
+    let mut entities = match_known_entities(message, &known, app_config.entities.max_ngram);
+
+    if app_config.entities.llm_assisted {
+        for name in extract_with_llm(message, app_config) {
+            // Resolve to the stored spelling when the graph already knows it.
+            let matched = match_known_entities(&name, &known, app_config.entities.max_ngram);
+            let name = match matched.first() {
+                Some(m) => m.name.clone(),
+                None => name,
+            };
+            if !entities.iter().any(|e| e.name.eq_ignore_ascii_case(&name)) {
+                entities.push(ExtractedEntity { name, score: 0.6 });
+            }
+        }
+    }
+
+    entities
+}
@@ -0,0 +1,298 @@
+use crate::context_builder::{chars_per_token, estimate_tokens};
+use crate::memory_service::MemoryService;
+use crate::planner::resolve_provider;
+use serde::Serialize;
+use serde_json::{json, Value};
+use shared_types::{ActionRequest, ActionResponse, ActionResult, ActionSpec, AppConfig, ModelPrice};
+use uuid::Uuid;
+
+// Estimation: predicts tokens, dollar cost and latency of a plan before it
+// runs. Token counts use the context builder's heuristic, prices come from
+// [estimation.prices] and latency from recent traces of the same agent
+// action. Used for dry runs and to enforce per-request ceilings, which are
+// checked against the worst case: every call retried as often as
+// self-correction or the workflow allows.
+
+// One agent call as the estimator sees it.
+#[derive(Debug, Clone)]
+pub struct PlannedCall {
+    pub tool: String,
+    pub action: String,
+    // (provider, model) for LLM calls
+    pub llm: Option<(String, String)>,
+    pub input_text: String,
+    // Output of the previous step that this call will also receive.
+    pub carried_tokens: usize,
+    // Most times the call may run, counting retries.
+    pub attempts: usize,
+}
+
+impl PlannedCall {
+    // LLM calls carry their provider settings in `payload.config`; calls to
+    // the LLM agent without them will use the default provider.
+    pub fn new(tool: &str, action: &str, context: &str, payload: &Value, app_config: &AppConfig) -> Self {
+        let text = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let llm = match payload.get("config") {
+            Some(c) => {
+                let field = |key: &str| c.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                Some((field("provider"), field("model_name")))
+            }
+            None => resolve_provider(tool, app_config).map(|(name, c)| (name, c.model_name)),
+        };
+        let input_text = [text("system"), text("prompt"), text("input"), context.to_string()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Self {
+            tool: tool.to_string(),
+            action: action.to_string(),
+            llm,
+            input_text,
+            carried_tokens: 0,
+            attempts: 1,
+        }
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.attempts = retries + 1;
+        self
+    }
+
+    pub fn from_request(request: &ActionRequest, app_config: &AppConfig) -> Self {
+        Self::new(&request.tool, &request.action, &request.context, &request.payload.0, app_config)
+    }
+
+    pub fn from_action(action: &ActionSpec, app_config: &AppConfig) -> Self {
+        Self::new(&action.tool, &action.action, &action.context, &action.payload, app_config)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StepEstimate {
+    pub tool: String,
+    pub action: String,
+    pub model: Option<String>,
+    // LLM tokens; zero for non-LLM agents.
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    // Expected size of the step's result, passed on to the next step.
+    pub result_tokens: usize,
+    pub cost_usd: Option<f64>,
+    pub latency_ms: u64,
+    // "history" or "default"
+    pub latency_source: String,
+    pub samples: usize,
+    // Tokens, cost and latency above are for one attempt.
+    pub attempts: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PlanEstimate {
+    pub steps: Vec<StepEstimate>,
+    pub parallel: bool,
+    pub total_tokens: usize,
+    // None when an LLM step has no price configured.
+    pub cost_usd: Option<f64>,
+    pub latency_ms: u64,
+    // Totals if every call uses all of its attempts.
+    pub worst_tokens: usize,
+    pub worst_cost_usd: Option<f64>,
+    pub worst_latency_ms: u64,
+    pub exceeded: Vec<String>,
+}
+
+fn price_for<'a>(app_config: &'a AppConfig, provider: &str, model: &str) -> Option<&'a ModelPrice> {
+    let prices = &app_config.estimation.prices;
+    prices.get(&format!("{}:{}", provider, model)).or_else(|| prices.get(model))
+}
+
+pub async fn estimate_call(call: &PlannedCall, memory_service: &MemoryService, app_config: &AppConfig) -> StepEstimate {
+    let config = &app_config.estimation;
+    let stats = memory_service
+        .call_stats(&call.tool, &call.action, config.history_window)
+        .await
+        .unwrap_or_default();
+
+    let model = call.llm.as_ref().map(|(_, m)| m.as_str()).unwrap_or_default();
+    let result_tokens = if stats.samples > 0 {
+        (stats.mean_output_chars as f32 / chars_per_token(model, &app_config.context)).ceil() as usize
+    } else {
+        config.default_output_tokens
+    };
+
+    let (input_tokens, output_tokens, cost_usd) = match &call.llm {
+        Some((provider, model)) => {
+            let input = estimate_tokens(&call.input_text, model, &app_config.context) + call.carried_tokens;
+            let cost = price_for(app_config, provider, model).map(|p| {
+                (input as f64 * p.input_per_mtok + result_tokens as f64 * p.output_per_mtok) / 1_000_000.0
+            });
+            (input, result_tokens, cost)
+        }
+        None => (0, 0, Some(0.0)),
+    };
+
+    let (latency_ms, latency_source) = if stats.samples > 0 {
+        (stats.p90_latency_ms, "history")
+    } else {
+        (config.default_latency_ms, "default")
+    };
+
+    StepEstimate {
+        tool: call.tool.clone(),
+        action: call.action.clone(),
+        model: call.llm.as_ref().map(|(_, m)| m.clone()),
+        input_tokens,
+        output_tokens,
+        result_tokens,
+        cost_usd,
+        latency_ms,
+        latency_source: latency_source.to_string(),
+        samples: stats.samples,
+        attempts: call.attempts.max(1),
+    }
+}
+
+// Sequential calls each receive the previous step's expected result.
+pub async fn estimate_sequence(
+    mut calls: Vec<PlannedCall>,
+    memory_service: &MemoryService,
+    app_config: &AppConfig,
+) -> PlanEstimate {
+    let mut steps: Vec<StepEstimate> = Vec::new();
+    for call in calls.iter_mut() {
+        if let Some(previous) = steps.last() {
+            call.carried_tokens = previous.result_tokens;
+        }
+        steps.push(estimate_call(call, memory_service, app_config).await);
+    }
+    combine(steps, false, app_config)
+}
+
+pub async fn estimate_parallel(
+    calls: Vec<PlannedCall>,
+    memory_service: &MemoryService,
+    app_config: &AppConfig,
+) -> PlanEstimate {
+    let mut steps = Vec::new();
+    for call in &calls {
+        steps.push(estimate_call(call, memory_service, app_config).await);
+    }
+    combine(steps, true, app_config)
+}
+
+// Totals for `attempts(step)` runs of each step.
+fn totals(steps: &[StepEstimate], parallel: bool, attempts: impl Fn(&StepEstimate) -> usize) -> (usize, Option<f64>, u64) {
+    let tokens = steps.iter().map(|s| (s.input_tokens + s.output_tokens) * attempts(s)).sum();
+    let cost = steps.iter().map(|s| s.cost_usd.map(|c| c * attempts(s) as f64)).sum::<Option<f64>>();
+    let latency = steps.iter().map(|s| s.latency_ms * attempts(s) as u64);
+    let latency = if parallel { latency.max().unwrap_or(0) } else { latency.sum() };
+    (tokens, cost, latency)
+}
+
+fn combine(steps: Vec<StepEstimate>, parallel: bool, app_config: &AppConfig) -> PlanEstimate {
+    let config = &app_config.estimation;
+    let (total_tokens, cost_usd, latency_ms) = totals(&steps, parallel, |_| 1);
+    let (worst_tokens, worst_cost_usd, worst_latency_ms) = totals(&steps, parallel, |s| s.attempts);
+    let retrying = if steps.iter().any(|s| s.attempts > 1) { " with retries" } else { "" };
+
+    let mut exceeded = Vec::new();
+    if let Some(max) = config.max_tokens.filter(|max| worst_tokens > *max) {
+        exceeded.push(format!("{} tokens{} exceeds the limit of {}", worst_tokens, retrying, max));
+    }
+    if let Some(max) = config.max_cost_usd {
+        match worst_cost_usd {
+            Some(cost) if cost > max => {
+                exceeded.push(format!("${:.4}{} exceeds the limit of ${:.4}", cost, retrying, max))
+            }
+            Some(_) => {}
+            // An unpriced model could cost anything
+            None => {
+                let unpriced: Vec<&str> = steps
+                    .iter()
+                    .filter(|s| s.cost_usd.is_none())
+                    .filter_map(|s| s.model.as_deref())
+                    .collect();
+                exceeded.push(format!(
+                    "cost unknown (no price for {}) with a limit of ${:.4}",
+                    unpriced.join(", "),
+                    max
+                ));
+            }
+        }
+    }
+    if let Some(max) = config.max_latency_ms.filter(|max| worst_latency_ms > *max) {
+        exceeded.push(format!("{} ms{} exceeds the limit of {} ms", worst_latency_ms, retrying, max));
+    }
+
+    PlanEstimate {
+        steps,
+        parallel,
+        total_tokens,
+        cost_usd,
+        latency_ms,
+        worst_tokens,
+        worst_cost_usd,
+        worst_latency_ms,
+        exceeded,
+    }
+}
+
+// Refuses plans over a configured ceiling.
+pub fn enforce(estimate: &PlanEstimate, app_config: &AppConfig) -> Result<(), String> {
+    if !app_config.estimation.enabled || estimate.exceeded.is_empty() {
+        return Ok(());
+    }
+    Err(format!("Plan not run: {}", estimate.exceeded.join("; ")))
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map(|c| format!("${:.4}", c)).unwrap_or_else(|| "unknown cost".to_string())
+}
+
+fn describe(estimate: &PlanEstimate) -> String {
+    let cost = format_cost(estimate.cost_usd);
+    let mut lines = vec![format!(
+        "Estimate: {} step(s){}, ~{} tokens, {}, ~{} ms",
+        estimate.steps.len(),
+        if estimate.parallel { " in parallel" } else { "" },
+        estimate.total_tokens,
+        cost,
+        estimate.latency_ms
+    )];
+    if estimate.steps.iter().any(|s| s.attempts > 1) {
+        lines.push(format!(
+            "With every retry: ~{} tokens, {}, ~{} ms",
+            estimate.worst_tokens,
+            format_cost(estimate.worst_cost_usd),
+            estimate.worst_latency_ms
+        ));
+    }
+    for step in &estimate.steps {
+        let attempts = if step.attempts > 1 { format!(", up to {} attempts", step.attempts) } else { String::new() };
+        lines.push(format!(
+            "- {} {}: {} in / {} out tokens, ~{} ms ({}){}",
+            step.tool, step.action, step.input_tokens, step.output_tokens, step.latency_ms, step.latency_source, attempts
+        ));
+    }
+    for limit in &estimate.exceeded {
+        lines.push(format!("! {}", limit));
+    }
+    lines.join("\n")
+}
+
+// Dry-run reply: nothing was executed.
+pub fn to_response(estimate: &PlanEstimate) -> ActionResponse {
+    ActionResponse {
+        request_id: Uuid::new_v4(),
+        status: "success".to_string(),
+        code: 0,
+        result: Some(ActionResult {
+            output_type: "estimate".to_string(),
+            data: describe(estimate),
+            metadata: Some(json!({ "estimate": estimate })),
+        }),
+        error: None,
+    }
+}
@@ -1,4 +1,6 @@
-use shared_types::{ActionRequest, ActionResponse};
+use shared_types::{ActionRequest, ActionResponse, AppConfig, Payload, ProviderConfig};
+use serde_json::json;
+use uuid::Uuid;
 use std::process::{Command, Stdio};
 use std::io::Write;
 
@@ -43,3 +45,53 @@ pub fn execute_agent(agent_name: &str, request: &ActionRequest) -> Result<Action
 
     Ok(response)
 }
+
+// The `config` block llm_router_agent expects in its payload.
+pub fn provider_config_json(provider: &str, config: &ProviderConfig) -> serde_json::Value {
+    json!({
+        "provider": provider,
+        "api_key": config.api_key,
+        "base_url": config.base_url,
+        "model_name": config.model_name
+    })
+}
+
+// One-shot prompt to the default LLM provider via llm_router_agent.
+// Used by internal pipelines (entity/fact extraction) rather than chat.
+pub fn call_llm_agent(app_config: &AppConfig, action: &str, prompt: &str) -> Result<String, String> {
+    let provider = &app_config.llm.default_provider;
+    let config = app_config
+        .llm
+        .provider(provider)
+        .ok_or_else(|| format!("No configuration for LLM provider '{}'", provider))?;
+
+    let request = ActionRequest {
+        request_id: Uuid::new_v4(),
+        tool: "llm_router_agent".to_string(),
+        action: action.to_string(),
+        context: String::new(),
+        payload: Payload(json!({
+            "prompt": prompt,
+            "config": provider_config_json(provider, config)
+        })),
+    };
+
+    let response = execute_agent("llm_router_agent", &request)?;
+    match response.result {
+        Some(result) if result.output_type != "error" => Ok(result.data),
+        Some(result) => Err(result.data),
+        None => Err(response.error.unwrap_or_else(|| "Empty LLM response".to_string())),
+    }
+}
+
+// Models like to wrap JSON in prose or code fences; take the outermost
+// span between `open` and `close`.
+pub fn extract_json_span(text: &str, open: char, close: char) -> Option<&str> {
+    let start = text.find(open)?;
+    let end = text.rfind(close)?;
+    if end > start {
+        Some(&text[start..=end])
+    } else {
+        None
+    }
+}
@@ -0,0 +1,195 @@
+use crate::executor::{call_llm_agent, extract_json_span};
+use crate::memory_service::{FactProvenance, FactStatus, MemoryService};
+use regex::Regex;
+use serde::Deserialize;
+use shared_types::AppConfig;
+use std::sync::{Arc, OnceLock};
+
+// Fact Extraction: turns statements in user messages and agent results into
+// knowledge-graph triples, recorded with the trace they came from.
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Triple {
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+}
+
+const PRONOUNS: &[&str] = &[
+    "i", "you", "he", "she", "it", "we", "they", "this", "that", "there", "here", "what", "who",
+    "which", "where", "when", "why", "how", "everything", "something", "nothing",
+];
+
+const NEGATIONS: &[&str] = &["not", "no", "never", "none", "nobody", "nothing", "neither", "nor", "cannot"];
+
+const MAX_TERM_WORDS: usize = 6;
+
+fn statement_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            // Multi-word predicates first, so "is located in" wins over "is"
+            r"(?i)^(?:my |our |the )?(.+?) (is located in|runs on|lives in|works at|belongs to|depends on|is|are|was|uses|runs) (?:a |an |the |my |our )?(.+)$",
+        )
+        .unwrap()
+    })
+}
+
+// A sentence ends at . ! or ? followed by whitespace (or the end of the
+// text), so hostnames and version numbers stay whole; newlines and
+// semicolons also end one.
+fn sentence_end_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"[.!?]+(?:\s+|$)|[\n;]").unwrap())
+}
+
+// Yields each sentence with whether it was a question.
+fn sentences(text: &str) -> Vec<(&str, bool)> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for end in sentence_end_regex().find_iter(text) {
+        sentences.push((&text[start..end.start()], end.as_str().contains('?')));
+        start = end.end();
+    }
+    sentences.push((&text[start..], false));
+    sentences
+}
+
+fn is_negated(sentence: &str) -> bool {
+    sentence.split(|c: char| !c.is_alphanumeric() && c != '\'').any(|word| {
+        let word = word.to_lowercase();
+        word.ends_with("n't") || NEGATIONS.contains(&word.as_str())
+    })
+}
+
+fn clean_term(term: &str) -> String {
+    term.trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '`' || c == ',')
+        .trim()
+        .to_string()
+}
+
+fn is_plausible_term(term: &str) -> bool {
+    let words: Vec<&str> = term.split_whitespace().collect();
+    !words.is_empty()
+        && words.len() <= MAX_TERM_WORDS
+        && !(words.len() == 1 && PRONOUNS.contains(&words[0].to_lowercase().as_str()))
+}
+
+// Rule-based extractor for simple "X is Y" style statements. Questions,
+// negated sentences and sentences with pronoun subjects are skipped.
+pub fn extract_rule_based(text: &str) -> Vec<Triple> {
+    let mut triples = Vec::new();
+
+    for (sentence, question) in sentences(text) {
+        let sentence = sentence.trim();
+        if sentence.is_empty() || question || sentence.contains('?') || is_negated(sentence) {
+            continue;
+        }
+        let Some(caps) = statement_regex().captures(sentence) else {
+            continue;
+        };
+
+        let subject = clean_term(&caps[1]);
+        let predicate = caps[2].to_lowercase();
+        let object = clean_term(&caps[3]);
+
+        if !is_plausible_term(&subject) || !is_plausible_term(&object) {
+            continue;
+        }
+        if PRONOUNS.contains(&subject.split_whitespace().next().unwrap_or("").to_lowercase().as_str()) {
+            continue;
+        }
+
+        let triple = Triple { subject, predicate, object };
+        if !triples.contains(&triple) {
+            triples.push(triple);
+        }
+    }
+
+    triples
+}
+
+// LLM extractor. Returns an empty list on any failure.
+pub fn extract_with_llm(text: &str, app_config: &AppConfig) -> Vec<Triple> {
+    let prompt = format!(
+        "Extract durable facts from the text below as subject-predicate-object triples. \
+         Only include facts that would still be true tomorrow; skip questions, opinions and requests. \
+         Respond with only a JSON array of objects with \"subject\", \"predicate\" and \"object\" string fields.\n\nText: {}",
+        text
+    );
+
+    call_llm_agent(app_config, "extract_facts", &prompt)
+        .ok()
+        .and_then(|data| extract_json_span(&data, '[', ']').and_then(|json| serde_json::from_str::<Vec<Triple>>(json).ok()))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| Triple {
+            subject: clean_term(&t.subject),
+            predicate: clean_term(&t.predicate).to_lowercase(),
+            object: clean_term(&t.object),
+        })
+        .filter(|t| is_plausible_term(&t.subject) && !t.predicate.is_empty() && is_plausible_term(&t.object))
+        .collect()
+}
+
+pub fn extract_triples(text: &str, app_config: &AppConfig) -> Vec<Triple> {
+    let mut triples = extract_rule_based(text);
+    if app_config.facts.llm_extraction {
+        for triple in extract_with_llm(text, app_config) {
+            if !triples.contains(&triple) {
+                triples.push(triple);
+            }
+        }
+    }
+    triples.truncate(app_config.facts.max_facts_per_text);
+    triples
+}
+
+// Post-response pipeline. Runs after the response is returned so extraction
+// (possibly an extra LLM round-trip) never delays the chat reply.
+pub fn spawn_fact_extraction(
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+    trace_id: String,
+    user_message: String,
+    agent_result: Option<String>,
+) {
+    if !app_config.facts.enabled {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut sources = vec![("user_message", user_message)];
+        if app_config.facts.extract_from_results {
+            if let Some(result) = agent_result {
+                sources.push(("agent_result", result));
+            }
+        }
+
+        for (origin, text) in sources {
+            let config = app_config.clone();
+            let triples = match tokio::task::spawn_blocking(move || extract_triples(&text, &config)).await {
+                Ok(triples) => triples,
+                Err(e) => {
+                    eprintln!("Fact extraction failed: {}", e);
+                    continue;
+                }
+            };
+
+            for triple in triples {
+                let provenance = FactProvenance {
+                    trace_id: Some(trace_id.clone()),
+                    origin: origin.to_string(),
+                    status: FactStatus::Unreviewed,
+                };
+                if let Err(e) = memory_service
+                    .add_knowledge_triple(&triple.subject, &triple.predicate, &triple.object, provenance)
+                    .await
+                {
+                    eprintln!("Failed to store extracted fact: {}", e);
+                }
+            }
+        }
+    });
+}
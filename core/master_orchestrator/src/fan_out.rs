@@ -0,0 +1,267 @@
+use crate::executor::{call_llm_agent, execute_agent};
+use crate::memory_service::{MemoryService, TraceMeta};
+use crate::self_correction::{self, failed_response};
+use serde::Serialize;
+use serde_json::json;
+use shared_types::{ActionRequest, ActionResponse, ActionResult, AppConfig, Payload};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+// Fan-Out: dispatches the same user request to several agents concurrently,
+// each under its own timeout, and merges their results into one response
+// with per-source attribution in `metadata.fan_out.sources`. Failed calls
+// go through self-correction when it is enabled; timed-out ones don't.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    Concat,
+    Synthesize,
+    FirstSuccess,
+}
+
+impl MergeStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeStrategy::Concat => "concat",
+            MergeStrategy::Synthesize => "synthesize",
+            MergeStrategy::FirstSuccess => "first_success",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "concat" => Some(MergeStrategy::Concat),
+            "synthesize" => Some(MergeStrategy::Synthesize),
+            "first_success" => Some(MergeStrategy::FirstSuccess),
+            _ => None,
+        }
+    }
+}
+
+// Outcome of one agent call, as shown to the UI.
+#[derive(Serialize, Debug, Clone)]
+pub struct SourceResult {
+    pub tool: String,
+    // The first attempt's trace; self-corrected retries link back to it.
+    pub trace_id: String,
+    // "success", "error", "timeout" or "skipped" (first_success only)
+    pub status: String,
+    pub output_type: String,
+    pub data: String,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+impl SourceResult {
+    fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+fn source_from_response(
+    request: &ActionRequest,
+    response: &ActionResponse,
+    trace_id: String,
+    latency_ms: u64,
+) -> SourceResult {
+    let result = response.result.as_ref();
+    let failed = self_correction::is_failure(response);
+    SourceResult {
+        tool: request.tool.clone(),
+        trace_id,
+        status: if failed { "error" } else { "success" }.to_string(),
+        output_type: result.map(|r| r.output_type.clone()).unwrap_or_default(),
+        data: result.map(|r| r.data.clone()).unwrap_or_default(),
+        error: response.error.clone().or_else(|| failed.then(|| result.map(|r| r.data.clone())).flatten()),
+        latency_ms,
+    }
+}
+
+// Runs one agent call with a timeout and logs its trace. A timed-out agent
+// process is left to finish on its blocking thread; its late result is
+// discarded.
+async fn run_one(
+    user_message: String,
+    request: ActionRequest,
+    timeout: Duration,
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+    trace_meta: TraceMeta,
+) -> SourceResult {
+    let started = Instant::now();
+    let request = Arc::new(request);
+    let call = {
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || execute_agent(&request.tool, &request))
+    };
+
+    let (response, timed_out) = match tokio::time::timeout(timeout, call).await {
+        Ok(Ok(Ok(response))) => (response, false),
+        Ok(Ok(Err(e))) => (failed_response(&request, e), false),
+        Ok(Err(e)) => (failed_response(&request, e.to_string()), false),
+        Err(_) => (failed_response(&request, format!("timed out after {} ms", timeout.as_millis())), true),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let trace_meta = TraceMeta {
+        latency_ms: Some(latency_ms as i64),
+        ..trace_meta
+    };
+
+    if let Err(e) = memory_service.log_action_trace(&request, &response, &trace_meta).await {
+        eprintln!("Failed to log action trace: {}", e);
+    }
+
+    let trace_id = request.request_id.to_string();
+    if timed_out {
+        let mut source = source_from_response(&request, &response, trace_id, latency_ms);
+        source.status = "timeout".to_string();
+        return source;
+    }
+    if app_config.self_correction.enabled && self_correction::is_failure(&response) {
+        // Usually the last reference once the blocking call has returned
+        let request = Arc::try_unwrap(request).unwrap_or_else(|shared| ActionRequest {
+            request_id: shared.request_id,
+            tool: shared.tool.clone(),
+            action: shared.action.clone(),
+            context: shared.context.clone(),
+            payload: Payload(shared.payload.0.clone()),
+        });
+        let (request, response) =
+            self_correction::correct(&user_message, request, response, &memory_service, &app_config, &trace_meta).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        return source_from_response(&request, &response, trace_id, latency_ms);
+    }
+    source_from_response(&request, &response, trace_id, latency_ms)
+}
+
+fn concat(sources: &[SourceResult]) -> String {
+    sources
+        .iter()
+        .map(|s| match s.status.as_str() {
+            "success" => format!("[{}]\n{}", s.tool, s.data),
+            status => format!("[{}] {}: {}", s.tool, status, s.error.clone().unwrap_or_default()),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn synthesize(user_message: &str, sources: &[SourceResult], app_config: &AppConfig) -> Result<String, String> {
+    let results = sources
+        .iter()
+        .filter(|s| s.is_success())
+        .map(|s| format!("--- {} ---\n{}", s.tool, s.data))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if results.is_empty() {
+        return Err("no agent succeeded".to_string());
+    }
+
+    let prompt = format!(
+        "Several agents answered the user's request. Combine their results into one answer. \
+         Mention which agent each piece of information came from.\n\n\
+         User request: {}\n\nResults:\n{}",
+        user_message, results
+    );
+    call_llm_agent(app_config, "synthesize", &prompt)
+}
+
+pub async fn dispatch(
+    user_message: &str,
+    requests: Vec<ActionRequest>,
+    memory_service: Arc<MemoryService>,
+    app_config: &Arc<AppConfig>,
+    trace_meta: &TraceMeta,
+) -> ActionResponse {
+    let config = &app_config.fan_out;
+    let strategy = MergeStrategy::parse(&config.merge).unwrap_or_else(|| {
+        eprintln!("Unknown fan-out merge '{}', using concat", config.merge);
+        MergeStrategy::Concat
+    });
+    let timeout = Duration::from_millis(config.timeout_ms);
+
+    // Keep the routing order for attribution, whatever the completion order.
+    let order: Vec<(String, String)> = requests.iter().map(|r| (r.tool.clone(), r.request_id.to_string())).collect();
+    let mut set = JoinSet::new();
+    for request in requests {
+        set.spawn(run_one(
+            user_message.to_string(),
+            request,
+            timeout,
+            memory_service.clone(),
+            app_config.clone(),
+            trace_meta.clone(),
+        ));
+    }
+
+    let mut finished: Vec<SourceResult> = Vec::new();
+    while let Some(joined) = set.join_next().await {
+        match joined {
+            Ok(source) => {
+                let done = strategy == MergeStrategy::FirstSuccess && source.is_success();
+                finished.push(source);
+                if done {
+                    // Let the remaining calls finish (and log) in the background.
+                    set.detach_all();
+                    break;
+                }
+            }
+            Err(e) => eprintln!("Fan-out task failed: {}", e),
+        }
+    }
+
+    let sources: Vec<SourceResult> = order
+        .into_iter()
+        .map(|(tool, trace_id)| {
+            finished.iter().find(|s| s.trace_id == trace_id).cloned().unwrap_or(SourceResult {
+                tool,
+                trace_id,
+                status: "skipped".to_string(),
+                output_type: String::new(),
+                data: String::new(),
+                error: None,
+                latency_ms: 0,
+            })
+        })
+        .collect();
+
+    let any_success = sources.iter().any(|s| s.is_success());
+    let (data, merge_error) = match strategy {
+        MergeStrategy::Concat => (concat(&sources), None),
+        MergeStrategy::FirstSuccess => match sources.iter().find(|s| s.is_success()) {
+            Some(s) => (format!("[{}]\n{}", s.tool, s.data), None),
+            None => (concat(&sources), None),
+        },
+        // Synthesis failures fall back to plain concatenation.
+        MergeStrategy::Synthesize => match synthesize(user_message, &sources, app_config) {
+            Ok(text) => (text, None),
+            Err(e) => (concat(&sources), Some(e)),
+        },
+    };
+
+    println!(
+        "Fan-out to {} agent(s) merged with {}: {}",
+        sources.len(),
+        strategy.as_str(),
+        sources.iter().map(|s| format!("{}={}", s.tool, s.status)).collect::<Vec<_>>().join(", ")
+    );
+
+    ActionResponse {
+        request_id: Uuid::new_v4(),
+        status: if any_success { "success" } else { "error" }.to_string(),
+        code: if any_success { 0 } else { 1 },
+        result: Some(ActionResult {
+            output_type: if any_success { "text" } else { "error" }.to_string(),
+            data,
+            metadata: Some(json!({
+                "fan_out": {
+                    "merge": strategy.as_str(),
+                    "merge_error": merge_error,
+                    "sources": sources,
+                }
+            })),
+        }),
+        error: None,
+    }
+}
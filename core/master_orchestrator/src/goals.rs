@@ -0,0 +1,241 @@
+use crate::embedder::Embedder;
+use crate::executor::call_llm_agent;
+use crate::memory_service::{cosine_similarity, MemoryService};
+use crate::scheduler::{from_timestamp, to_timestamp};
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use shared_types::AppConfig;
+use std::sync::Arc;
+
+// Goals: long-running objectives that persist across sessions. Active goals
+// are offered to the planner as context, chat traces that relate to a goal
+// are linked to it, and periodic check-ins summarise the linked activity
+// into a progress note.
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalStatus {
+    #[default]
+    Active,
+    Paused,
+    Done,
+    Abandoned,
+}
+
+impl GoalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalStatus::Active => "active",
+            GoalStatus::Paused => "paused",
+            GoalStatus::Done => "done",
+            GoalStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(GoalStatus::Active),
+            "paused" => Some(GoalStatus::Paused),
+            "done" => Some(GoalStatus::Done),
+            "abandoned" => Some(GoalStatus::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Goal {
+    #[serde(default)]
+    pub id: i64,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub status: GoalStatus,
+    // Free-form deadline, e.g. "2026-10-23" or "Friday".
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub last_check_in_at: Option<String>,
+}
+
+impl Goal {
+    pub fn summary(&self) -> String {
+        match &self.due {
+            Some(due) => format!("{} (due {})", self.title, due),
+            None => self.title.clone(),
+        }
+    }
+
+    fn embedding_text(&self) -> String {
+        format!("{} {}", self.title, self.description)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GoalNote {
+    pub id: i64,
+    pub goal_id: i64,
+    // "note" (added by the user) or "check_in" (generated)
+    pub kind: String,
+    pub text: String,
+    pub created_at: String,
+}
+
+// A linked trace, flattened for summaries.
+#[derive(Serialize, Debug, Clone)]
+pub struct GoalActivity {
+    pub trace_id: String,
+    pub linked_at: String,
+    pub tool: String,
+    pub action: String,
+    pub prompt: String,
+    pub result: String,
+}
+
+pub fn validate(goal: &Goal) -> Result<(), String> {
+    if goal.title.trim().is_empty() {
+        return Err("Goal title must not be empty".to_string());
+    }
+    Ok(())
+}
+
+// How related a message is to each goal, using the semantic-memory
+// embedder. The message and goals are embedded in one call.
+pub async fn relevance(goals: &[Goal], text: &str, embedder: &dyn Embedder) -> Result<Vec<f32>, String> {
+    let texts: Vec<String> = std::iter::once(text.to_string()).chain(goals.iter().map(Goal::embedding_text)).collect();
+    let vectors = embedder.embed(&texts).await?;
+    if vectors.len() != texts.len() {
+        return Err(format!("Embedder returned {} vectors for {} texts", vectors.len(), texts.len()));
+    }
+    Ok(vectors[1..].iter().map(|goal| cosine_similarity(&vectors[0], goal)).collect())
+}
+
+// Links the traces of one chat exchange to every active goal it relates to.
+pub fn spawn_goal_linking(
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+    user_message: String,
+    trace_ids: Vec<String>,
+) {
+    if !app_config.goals.auto_link {
+        return;
+    }
+    tokio::spawn(async move {
+        let goals = match memory_service.list_goals(Some(GoalStatus::Active)).await {
+            Ok(goals) => goals,
+            Err(e) => {
+                eprintln!("Goal linking failed: {}", e);
+                return;
+            }
+        };
+        let scores = match relevance(&goals, &user_message, memory_service.embedder().as_ref()).await {
+            Ok(scores) => scores,
+            Err(e) => {
+                eprintln!("Goal linking failed: {}", e);
+                return;
+            }
+        };
+        for (goal, score) in goals.iter().zip(scores) {
+            if score < app_config.goals.link_threshold {
+                continue;
+            }
+            for trace_id in &trace_ids {
+                if let Err(e) = memory_service.link_goal_trace(goal.id, trace_id).await {
+                    eprintln!("Failed to link trace to goal {}: {}", goal.id, e);
+                }
+            }
+        }
+    });
+}
+
+fn describe_activity(activity: &[GoalActivity]) -> String {
+    activity
+        .iter()
+        .map(|a| {
+            let result: String = a.result.chars().take(200).collect();
+            format!("- {} {} {}: \"{}\" -> {}", a.linked_at, a.tool, a.action, a.prompt, result)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Summarises activity linked since the last check-in and stores it as a
+// "check_in" note. Falls back to a plain listing if the LLM is unavailable.
+pub async fn check_in(goal: &Goal, memory_service: &MemoryService, app_config: &AppConfig) -> Result<GoalNote, String> {
+    let now = to_timestamp(Local::now());
+    let activity = memory_service
+        .goal_activity(goal.id, goal.last_check_in_at.as_deref(), app_config.goals.max_check_in_traces)
+        .await?;
+
+    let text = if activity.is_empty() {
+        "No linked activity since the last check-in.".to_string()
+    } else {
+        let listing = describe_activity(&activity);
+        let notes = memory_service.get_goal_notes(goal.id, 5).await.unwrap_or_default();
+        let previous = notes.iter().map(|n| format!("- {}", n.text)).collect::<Vec<_>>().join("\n");
+        let prompt = format!(
+            "Summarise progress toward this goal in 2-4 sentences, based on the recent actions. \
+             Note anything blocking and what is left to do.\n\n\
+             Goal: {}\n{}\n\nRecent notes:\n{}\n\nRecent actions:\n{}",
+            goal.summary(),
+            goal.description,
+            previous,
+            listing
+        );
+        match call_llm_agent(app_config, "check_in", &prompt) {
+            Ok(summary) => summary,
+            Err(e) => {
+                eprintln!("Goal check-in summary failed, listing activity instead: {}", e);
+                format!("{} linked action(s) since the last check-in:\n{}", activity.len(), listing)
+            }
+        }
+    };
+
+    let note = memory_service.add_goal_note(goal.id, "check_in", &text).await?;
+    memory_service.record_goal_check_in(goal.id, &now).await?;
+    Ok(note)
+}
+
+fn is_check_in_due(goal: &Goal, interval: Duration) -> bool {
+    let last = goal.last_check_in_at.as_deref().or(goal.created_at.as_deref());
+    match last.and_then(from_timestamp) {
+        Some(last) => Local::now() - last >= interval,
+        None => true,
+    }
+}
+
+pub fn spawn_check_ins(memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    let hours = app_config.goals.check_in_hours;
+    if hours == 0 {
+        println!("Goal check-ins disabled.");
+        return;
+    }
+    let interval = Duration::hours(hours as i64);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let goals = match memory_service.list_goals(Some(GoalStatus::Active)).await {
+                Ok(goals) => goals,
+                Err(e) => {
+                    eprintln!("Goal check-in failed: {}", e);
+                    continue;
+                }
+            };
+            for goal in goals.iter().filter(|g| is_check_in_due(g, interval)) {
+                match check_in(goal, &memory_service, &app_config).await {
+                    Ok(_) => println!("Checked in on goal '{}'", goal.title),
+                    Err(e) => eprintln!("Check-in for goal '{}' failed: {}", goal.title, e),
+                }
+            }
+        }
+    });
+}
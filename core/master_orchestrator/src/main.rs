@@ -6,13 +6,46 @@ mod executor;
 mod planner;
 mod memory_service;
 mod config_service;
+mod context_builder;
+mod entity_extractor;
+mod fact_extractor;
+mod memory_commands;
+mod prompt_templates;
+mod cron;
+mod scheduler;
+mod watcher;
+mod self_correction;
+mod router;
+mod router_eval;
+mod fan_out;
+mod workflow;
+mod pipeline;
+mod goals;
+mod estimator;
+mod vector_index;
+mod embedding;
+mod embedder;
+mod reindex;
+mod memory_expiry;
+mod chunker;
+mod quantize;
+mod quantization_report;
 
-use memory_service::MemoryService;
+use memory_service::{FactStatus, MemoryFilter, MemoryService, TraceMeta};
+use scheduler::ScheduledTask;
+use goals::{Goal, GoalStatus};
 
 #[derive(serde::Deserialize, Debug)]
 pub struct ChatPayload {
     pub message: String,
     pub context: Option<String>,
+    pub session_id: Option<String>,
+    // Selects a named prompt template for this message and, within a
+    // session, the later ones too.
+    pub template: Option<String>,
+    // Estimate tokens, cost and latency without running anything.
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(serde::Serialize)]
@@ -30,10 +63,37 @@ async fn chat_endpoint(
     app_config: web::Data<Arc<AppConfig>>,
 ) -> Result<HttpResponse, Error> {
     println!("Received chat request: {:?}", payload);
+
+    if let Some(template) = &payload.template {
+        if !app_config.prompts.templates.contains_key(template) {
+            let chat_response = ChatResponse {
+                status: "error".to_string(),
+                output: format!("Unknown prompt template: {}", template),
+            };
+            return Ok(HttpResponse::Ok().json(chat_response));
+        }
+        if let Some(session_id) = &payload.session_id {
+            if let Err(e) = memory_service.set_session_template(session_id, template).await {
+                eprintln!("Failed to set session template: {}", e);
+            }
+        }
+    }
     
     // Use the existing planner logic
-    match planner::plan_and_execute(payload.message.clone(), memory_service.get_ref().clone(), app_config.get_ref().clone()).await {
+    match planner::plan_and_execute(payload.message.clone(), payload.session_id.clone(), payload.template.clone(), payload.dry_run, memory_service.get_ref().clone(), app_config.get_ref().clone(), TraceMeta::default()).await {
         Ok(response) => {
+            // Estimates describe a plan that never ran; keep them out of history.
+            let estimated = response.result.as_ref().is_some_and(|r| r.output_type == "estimate");
+            if let Some(session_id) = payload.session_id.as_ref().filter(|_| !estimated) {
+                let reply = response.result.as_ref().map(|r| r.data.clone()).unwrap_or_default();
+                if let Err(e) = memory_service.append_session_message(session_id, "user", &payload.message).await {
+                    eprintln!("Failed to record session history: {}", e);
+                }
+                if let Err(e) = memory_service.append_session_message(session_id, "assistant", &reply).await {
+                    eprintln!("Failed to record session history: {}", e);
+                }
+            }
+
             let chat_response = ChatResponse {
                 status: "success".to_string(),
                 output: serde_json::to_string(&response.result).unwrap_or_default(),
@@ -50,6 +110,527 @@ async fn chat_endpoint(
     }
 }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct FactQuery {
+    pub status: Option<String>,
+}
+
+// --- Knowledge Graph review ---
+async fn list_facts_endpoint(
+    query: web::Query<FactQuery>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let status = match query.status.as_deref() {
+        Some(s) => match FactStatus::parse(s) {
+            Some(status) => Some(status),
+            None => return Ok(HttpResponse::BadRequest().body(format!("Unknown status: {}", s))),
+        },
+        None => None,
+    };
+
+    match memory_service.list_knowledge_triples(status).await {
+        Ok(triples) => Ok(HttpResponse::Ok().json(triples)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn review_fact(memory_service: &MemoryService, id: i64, status: FactStatus) -> HttpResponse {
+    match memory_service.set_triple_status(id, status).await {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({"id": id, "status": status.as_str()})),
+        Ok(false) => HttpResponse::NotFound().body(format!("No fact with id {}", id)),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+async fn approve_fact_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    Ok(review_fact(memory_service.get_ref(), path.into_inner(), FactStatus::Confirmed).await)
+}
+
+async fn reject_fact_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    Ok(review_fact(memory_service.get_ref(), path.into_inner(), FactStatus::Rejected).await)
+}
+
+// --- Scheduled Tasks ---
+async fn list_schedules_endpoint(memory_service: web::Data<Arc<MemoryService>>) -> Result<HttpResponse, Error> {
+    match memory_service.list_scheduled_tasks(None).await {
+        Ok(tasks) => Ok(HttpResponse::Ok().json(tasks)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn find_schedule(memory_service: &MemoryService, id: i64) -> Result<Option<ScheduledTask>, String> {
+    Ok(memory_service.list_scheduled_tasks(None).await?.into_iter().find(|t| t.id == id))
+}
+
+async fn get_schedule_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match find_schedule(memory_service.get_ref(), id).await {
+        Ok(Some(task)) => Ok(HttpResponse::Ok().json(task)),
+        Ok(None) => Ok(HttpResponse::NotFound().body(format!("No scheduled task with id {}", id))),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn create_schedule_endpoint(
+    task: web::Json<ScheduledTask>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let mut task = task.into_inner();
+    task.last_run_at = None;
+    if let Err(e) = scheduler::prepare_task(&mut task) {
+        return Ok(HttpResponse::BadRequest().body(e));
+    }
+    match memory_service.create_scheduled_task(&task).await {
+        Ok(id) => {
+            task.id = id;
+            Ok(HttpResponse::Created().json(task))
+        }
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn update_schedule_endpoint(
+    path: web::Path<i64>,
+    task: web::Json<ScheduledTask>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let mut task = task.into_inner();
+    task.id = path.into_inner();
+    if let Err(e) = scheduler::prepare_task(&mut task) {
+        return Ok(HttpResponse::BadRequest().body(e));
+    }
+    match memory_service.update_scheduled_task(&task).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(task)),
+        Ok(false) => Ok(HttpResponse::NotFound().body(format!("No scheduled task with id {}", task.id))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn delete_schedule_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match memory_service.delete_scheduled_task(id).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Ok(HttpResponse::NotFound().body(format!("No scheduled task with id {}", id))),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn schedule_runs_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    match memory_service.get_schedule_runs(path.into_inner(), 50).await {
+        Ok(runs) => Ok(HttpResponse::Ok().json(runs)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// Runs a task immediately without changing its schedule.
+async fn run_schedule_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+    app_config: web::Data<Arc<AppConfig>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    let task = match find_schedule(memory_service.get_ref(), id).await {
+        Ok(Some(task)) => task,
+        Ok(None) => return Ok(HttpResponse::NotFound().body(format!("No scheduled task with id {}", id))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().body(e)),
+    };
+    match scheduler::run_task(&task, memory_service.get_ref().clone(), app_config.get_ref().clone()).await {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// --- Goals ---
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GoalQuery {
+    pub status: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct GoalDetail {
+    #[serde(flatten)]
+    goal: Goal,
+    notes: Vec<goals::GoalNote>,
+    activity: Vec<goals::GoalActivity>,
+}
+
+async fn list_goals_endpoint(
+    query: web::Query<GoalQuery>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let status = match query.status.as_deref() {
+        Some(s) => match GoalStatus::parse(s) {
+            Some(status) => Some(status),
+            None => return Ok(HttpResponse::BadRequest().body(format!("Unknown goal status: {}", s))),
+        },
+        None => None,
+    };
+    match memory_service.list_goals(status).await {
+        Ok(goals) => Ok(HttpResponse::Ok().json(goals)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn find_goal(memory_service: &MemoryService, id: i64) -> Result<Option<Goal>, String> {
+    Ok(memory_service.list_goals(None).await?.into_iter().find(|g| g.id == id))
+}
+
+async fn get_goal_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    let goal = match find_goal(memory_service.get_ref(), id).await {
+        Ok(Some(goal)) => goal,
+        Ok(None) => return Ok(HttpResponse::NotFound().body(format!("No goal with id {}", id))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().body(e)),
+    };
+    let notes = memory_service.get_goal_notes(id, 50).await;
+    let activity = memory_service.goal_activity(id, None, 50).await;
+    match (notes, activity) {
+        (Ok(notes), Ok(activity)) => Ok(HttpResponse::Ok().json(GoalDetail { goal, notes, activity })),
+        (Err(e), _) | (_, Err(e)) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn create_goal_endpoint(
+    goal: web::Json<Goal>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let goal = goal.into_inner();
+    if let Err(e) = goals::validate(&goal) {
+        return Ok(HttpResponse::BadRequest().body(e));
+    }
+    match memory_service.create_goal(&goal).await {
+        Ok(id) => match find_goal(memory_service.get_ref(), id).await {
+            Ok(Some(goal)) => Ok(HttpResponse::Created().json(goal)),
+            Ok(None) => Ok(HttpResponse::InternalServerError().body("Goal vanished after insert")),
+            Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+        },
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn update_goal_endpoint(
+    path: web::Path<i64>,
+    goal: web::Json<Goal>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let mut goal = goal.into_inner();
+    goal.id = path.into_inner();
+    if let Err(e) = goals::validate(&goal) {
+        return Ok(HttpResponse::BadRequest().body(e));
+    }
+    match memory_service.update_goal(&goal).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(goal)),
+        Ok(false) => Ok(HttpResponse::NotFound().body(format!("No goal with id {}", goal.id))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn delete_goal_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match memory_service.delete_goal(id).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Ok(HttpResponse::NotFound().body(format!("No goal with id {}", id))),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GoalNotePayload {
+    pub text: String,
+}
+
+async fn add_goal_note_endpoint(
+    path: web::Path<i64>,
+    payload: web::Json<GoalNotePayload>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match find_goal(memory_service.get_ref(), id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(HttpResponse::NotFound().body(format!("No goal with id {}", id))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().body(e)),
+    }
+    match memory_service.add_goal_note(id, "note", &payload.text).await {
+        Ok(note) => Ok(HttpResponse::Created().json(note)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GoalTracePayload {
+    pub trace_id: String,
+}
+
+async fn link_goal_trace_endpoint(
+    path: web::Path<i64>,
+    payload: web::Json<GoalTracePayload>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match find_goal(memory_service.get_ref(), id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(HttpResponse::NotFound().body(format!("No goal with id {}", id))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().body(e)),
+    }
+    match memory_service.link_goal_trace(id, &payload.trace_id).await {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// Runs a check-in now, regardless of when the last one was.
+async fn goal_check_in_endpoint(
+    path: web::Path<i64>,
+    memory_service: web::Data<Arc<MemoryService>>,
+    app_config: web::Data<Arc<AppConfig>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    let goal = match find_goal(memory_service.get_ref(), id).await {
+        Ok(Some(goal)) => goal,
+        Ok(None) => return Ok(HttpResponse::NotFound().body(format!("No goal with id {}", id))),
+        Err(e) => return Ok(HttpResponse::InternalServerError().body(e)),
+    };
+    match goals::check_in(&goal, memory_service.get_ref(), app_config.get_ref()).await {
+        Ok(note) => Ok(HttpResponse::Ok().json(note)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// --- Workflows ---
+
+#[derive(serde::Serialize)]
+struct WorkflowSummary {
+    name: String,
+    description: String,
+    inputs: Vec<shared_types::WorkflowInput>,
+    steps: Vec<String>,
+}
+
+async fn list_workflows_endpoint(app_config: web::Data<Arc<AppConfig>>) -> Result<HttpResponse, Error> {
+    let mut workflows: Vec<WorkflowSummary> = app_config
+        .workflows
+        .iter()
+        .map(|(name, w)| WorkflowSummary {
+            name: name.clone(),
+            description: w.description.clone(),
+            inputs: w.inputs.clone(),
+            steps: w.steps.iter().map(|s| s.name.clone()).collect(),
+        })
+        .collect();
+    workflows.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(HttpResponse::Ok().json(workflows))
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct WorkflowRunPayload {
+    #[serde(default)]
+    pub inputs: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+async fn run_workflow_endpoint(
+    path: web::Path<String>,
+    payload: Option<web::Json<WorkflowRunPayload>>,
+    memory_service: web::Data<Arc<MemoryService>>,
+    app_config: web::Data<Arc<AppConfig>>,
+) -> Result<HttpResponse, Error> {
+    let name = path.into_inner();
+    if !app_config.workflows.contains_key(&name) {
+        return Ok(HttpResponse::NotFound().body(format!("Unknown workflow: {}", name)));
+    }
+    let payload = payload.map(|p| p.into_inner()).unwrap_or_default();
+    let inputs = payload.inputs;
+    if payload.dry_run {
+        return match workflow::estimate(&name, inputs, memory_service.get_ref(), app_config.get_ref()).await {
+            Ok(estimate) => Ok(HttpResponse::Ok().json(estimate)),
+            Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+        };
+    }
+    match workflow::run(&name, inputs, memory_service.get_ref().clone(), app_config.get_ref().clone(), TraceMeta::default()).await {
+        Ok(run) => Ok(HttpResponse::Ok().json(run)),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn workflow_runs_endpoint(
+    path: web::Path<String>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    match memory_service.get_workflow_runs(&path.into_inner(), 50).await {
+        Ok(runs) => Ok(HttpResponse::Ok().json(runs)),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// --- Semantic memory search ---
+
+#[derive(serde::Deserialize, Debug)]
+pub struct MemorySearchPayload {
+    pub query: String,
+    #[serde(default)]
+    pub k: Option<usize>,
+    // Overrides [retrieval] return_parents.
+    #[serde(default)]
+    pub return_parents: Option<bool>,
+    #[serde(default, flatten)]
+    pub filter: MemoryFilter,
+}
+
+async fn search_memory_endpoint(
+    payload: web::Json<MemorySearchPayload>,
+    memory_service: web::Data<Arc<MemoryService>>,
+    app_config: web::Data<Arc<AppConfig>>,
+) -> Result<HttpResponse, Error> {
+    let payload = payload.into_inner();
+    let k = payload.k.unwrap_or(app_config.context.max_semantic_memories);
+    let mut retrieval = app_config.retrieval.clone();
+    if let Some(return_parents) = payload.return_parents {
+        retrieval.return_parents = return_parents;
+    }
+    match memory_service
+        .retrieve_semantic_context(&payload.query, k, &retrieval, &payload.filter)
+        .await
+    {
+        Ok(hits) => Ok(HttpResponse::Ok().json(hits)),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+async fn delete_memory_endpoint(
+    path: web::Path<String>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    let id = path.into_inner();
+    match memory_service.delete_semantic_memory(&id).await {
+        Ok(true) => Ok(HttpResponse::NoContent().finish()),
+        Ok(false) => Ok(HttpResponse::NotFound().body(format!("No memory with id {}", id))),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+// Deletes every memory matching a filter (same fields as search).
+async fn delete_memories_endpoint(
+    filter: web::Json<MemoryFilter>,
+    memory_service: web::Data<Arc<MemoryService>>,
+) -> Result<HttpResponse, Error> {
+    match memory_service.delete_semantic_memories(&filter.into_inner()).await {
+        Ok(deleted) => Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": deleted }))),
+        Err(e) => Ok(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+// --- Memory reindex ---
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct ReindexPayload {
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+}
+
+async fn start_reindex_endpoint(
+    payload: Option<web::Json<ReindexPayload>>,
+    memory_service: web::Data<Arc<MemoryService>>,
+    app_config: web::Data<Arc<AppConfig>>,
+) -> Result<HttpResponse, Error> {
+    let batch_size = payload
+        .and_then(|p| p.into_inner().batch_size)
+        .unwrap_or(app_config.embedding.batch_size);
+    match reindex::spawn(memory_service.get_ref().clone(), batch_size).await {
+        Ok(()) => Ok(HttpResponse::Accepted().json(serde_json::json!({
+            "status": "started",
+            "embedder": memory_service.embedder_id().to_string(),
+            "batch_size": batch_size,
+        }))),
+        Err(e) => Ok(HttpResponse::Conflict().body(e)),
+    }
+}
+
+async fn reindex_status_endpoint(memory_service: web::Data<Arc<MemoryService>>) -> Result<HttpResponse, Error> {
+    let status = match memory_service.reindex_locked().await {
+        Ok(running) => memory_service.latest_reindex_job().await.map(|job| (running, job)),
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok((running, job)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "running": running,
+            "embedder": memory_service.embedder_id().to_string(),
+            "job": job,
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().body(e)),
+    }
+}
+
+async fn run_cli(
+    command: &str,
+    args: &[String],
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+) -> Result<(), String> {
+    match command {
+        // eval-routers <labeled.json>...
+        "eval-routers" => {
+            let paths = if args.is_empty() { vec!["./data/routing_eval.json".to_string()] } else { args.to_vec() };
+            let cases = router_eval::load_cases(&paths)?;
+            let agents = memory_service.get_active_agents().await?;
+            let reports = router_eval::evaluate(&cases, &agents, &app_config, &memory_service.embedder()).await?;
+            println!("{}", serde_json::to_string_pretty(&reports).map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        // reindex [batch_size]
+        "reindex" => {
+            let batch_size = match args.first() {
+                Some(n) => n.parse().map_err(|_| format!("Invalid batch size '{}'", n))?,
+                None => app_config.embedding.batch_size,
+            };
+            let job = reindex::run(&memory_service, batch_size).await?;
+            println!("{}", serde_json::to_string_pretty(&job).map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        // quantization-report [k] [queries]
+        "quantization-report" => {
+            let number = |i: usize, default: usize| match args.get(i) {
+                Some(n) => n.parse().map_err(|_| format!("Invalid number '{}'", n)),
+                None => Ok(default),
+            };
+            let (k, queries) = (number(0, 10)?, number(1, 100)?);
+            let vectors = memory_service.indexed_vectors().await?;
+            let report = quantization_report::evaluate(
+                &memory_service.embedder_id(),
+                &vectors,
+                k,
+                queries,
+                app_config.memory.quantization.oversample,
+            )?;
+            println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+            Ok(())
+        }
+        other => Err(format!("Unknown command '{}'. Available: eval-routers, reindex, quantization-report", other)),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Load .env file
@@ -73,7 +654,14 @@ async fn main() -> std::io::Result<()> {
 
     // Initialize GAI Memory
     let db_path = "./data/memory_kg.db";
-    let memory_service = match MemoryService::new(db_path) {
+    let embedder = match embedder::build_embedder_with_retry(&app_config).await {
+        Ok(embedder) => embedder,
+        Err(e) => {
+            eprintln!("Failed to set up the '{}' embedder: {}", app_config.embedding.embedder, e);
+            return Ok(());
+        }
+    };
+    let memory_service = match MemoryService::new(db_path, embedder, app_config.memory.clone()) {
         Ok(service) => Arc::new(service),
         Err(e) => {
             eprintln!("Failed to initialize memory service: {}", e);
@@ -86,6 +674,14 @@ async fn main() -> std::io::Result<()> {
         return Ok(());
     }
     println!("GAI Memory initialized at {}", db_path);
+    if let Ok(Some(job)) = memory_service.latest_reindex_job().await {
+        if job.status == "running" {
+            println!(
+                "Reindex generation {} ({}) was interrupted at {}/{}; run `reindex` or POST /api/memory/reindex to resume",
+                job.generation, job.embedder, job.staged, job.total
+            );
+        }
+    }
 
     // Register Agents
     if let Err(e) = memory_service.register_agent("git_agent", "git_agent", "Handles Git operations").await {
@@ -106,6 +702,22 @@ async fn main() -> std::io::Result<()> {
         Err(e) => eprintln!("Failed to get active agents: {}", e),
     }
 
+    // CLI subcommands run against the same config and memory, then exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        if let Err(e) = run_cli(command, &args[1..], memory_service.clone(), app_config.clone()).await {
+            eprintln!("{}", e);
+        }
+        return Ok(());
+    }
+
+    // Start background subsystems
+    scheduler::spawn_scheduler(memory_service.clone(), app_config.clone());
+    watcher::spawn_watchers(memory_service.clone(), app_config.clone());
+    goals::spawn_check_ins(memory_service.clone(), app_config.clone());
+    memory_expiry::spawn_expiry(memory_service.clone(), app_config.clone());
+    reindex::spawn_refresh(memory_service.clone(), app_config.embedding.batch_size);
+
     // --- (A) BINDING TO THE PERMANENT PORT ---
     const BIND_ADDRESS: &str = "127.0.0.1:8181";
     println!("🚀 Starting API server on: {}", BIND_ADDRESS);
@@ -127,6 +739,32 @@ async fn main() -> std::io::Result<()> {
             .app_data(memory_data.clone())
             .app_data(config_data.clone())
             .route("/api/chat", web::post().to(chat_endpoint))
+            .route("/api/facts", web::get().to(list_facts_endpoint))
+            .route("/api/facts/{id}/approve", web::post().to(approve_fact_endpoint))
+            .route("/api/facts/{id}/reject", web::post().to(reject_fact_endpoint))
+            .route("/api/schedules", web::get().to(list_schedules_endpoint))
+            .route("/api/schedules", web::post().to(create_schedule_endpoint))
+            .route("/api/schedules/{id}", web::get().to(get_schedule_endpoint))
+            .route("/api/schedules/{id}", web::put().to(update_schedule_endpoint))
+            .route("/api/schedules/{id}", web::delete().to(delete_schedule_endpoint))
+            .route("/api/schedules/{id}/runs", web::get().to(schedule_runs_endpoint))
+            .route("/api/schedules/{id}/run", web::post().to(run_schedule_endpoint))
+            .route("/api/goals", web::get().to(list_goals_endpoint))
+            .route("/api/goals", web::post().to(create_goal_endpoint))
+            .route("/api/goals/{id}", web::get().to(get_goal_endpoint))
+            .route("/api/goals/{id}", web::put().to(update_goal_endpoint))
+            .route("/api/goals/{id}", web::delete().to(delete_goal_endpoint))
+            .route("/api/goals/{id}/notes", web::post().to(add_goal_note_endpoint))
+            .route("/api/goals/{id}/traces", web::post().to(link_goal_trace_endpoint))
+            .route("/api/goals/{id}/check-in", web::post().to(goal_check_in_endpoint))
+            .route("/api/memory/search", web::post().to(search_memory_endpoint))
+            .route("/api/memory/delete", web::post().to(delete_memories_endpoint))
+            .route("/api/memory/{id}", web::delete().to(delete_memory_endpoint))
+            .route("/api/memory/reindex", web::get().to(reindex_status_endpoint))
+            .route("/api/memory/reindex", web::post().to(start_reindex_endpoint))
+            .route("/api/workflows", web::get().to(list_workflows_endpoint))
+            .route("/api/workflows/{name}/run", web::post().to(run_workflow_endpoint))
+            .route("/api/workflows/{name}/runs", web::get().to(workflow_runs_endpoint))
             .service(actix_files::Files::new("/", "./frontend").index_file("index.html"))
     })
     .bind(BIND_ADDRESS)?
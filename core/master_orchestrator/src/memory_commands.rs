@@ -0,0 +1,245 @@
+use crate::fact_extractor::extract_rule_based;
+use crate::memory_service::{FactProvenance, FactStatus, MemoryMetadata, MemoryService, MemorySource};
+use regex::Regex;
+use serde_json::json;
+use shared_types::{ActionResponse, ActionResult};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+// Memory Commands: deterministic "remember / forget / recall" handling that
+// bypasses intent detection and writes straight through MemoryService.
+
+const MAX_RECALLED_NOTES: usize = 5;
+// Forgetting more notes than this needs "/forget! <target>"; without it
+// the matching notes are only listed.
+const MAX_UNCONFIRMED_FORGET: usize = 3;
+const MAX_LISTED_NOTES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryCommand {
+    Remember(String),
+    Forget { target: String, confirmed: bool },
+    Recall(String),
+}
+
+type CommandBuilder = fn(String) -> MemoryCommand;
+
+fn forget_unconfirmed(target: String) -> MemoryCommand {
+    MemoryCommand::Forget { target, confirmed: false }
+}
+
+fn natural_patterns() -> &'static [(Regex, CommandBuilder)] {
+    static PATTERNS: OnceLock<Vec<(Regex, CommandBuilder)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        vec![
+            (
+                Regex::new(r"(?i)^(?:please\s+)?remember\s+(?:that\s+)?(.+)$").unwrap(),
+                MemoryCommand::Remember as CommandBuilder,
+            ),
+            (
+                Regex::new(r"(?i)^(?:please\s+)?forget\s+(?:what\s+i\s+said\s+about|everything\s+about|about|that)\s+(.+)$").unwrap(),
+                forget_unconfirmed,
+            ),
+            (
+                Regex::new(r"(?i)^what\s+do\s+you\s+(?:remember|know)\s+about\s+(.+)$").unwrap(),
+                MemoryCommand::Recall,
+            ),
+        ]
+    })
+}
+
+fn clean_argument(arg: &str) -> String {
+    arg.trim().trim_end_matches(['.', '?', '!']).trim().to_string()
+}
+
+pub fn parse(message: &str) -> Option<MemoryCommand> {
+    let message = message.trim();
+
+    if let Some(rest) = message.strip_prefix('/') {
+        let (name, arg) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let arg = clean_argument(arg);
+        if arg.is_empty() {
+            return None;
+        }
+        return match name.to_lowercase().as_str() {
+            "remember" => Some(MemoryCommand::Remember(arg)),
+            "forget" => Some(MemoryCommand::Forget { target: arg, confirmed: false }),
+            "forget!" => Some(MemoryCommand::Forget { target: arg, confirmed: true }),
+            "recall" | "memories" => Some(MemoryCommand::Recall(arg)),
+            _ => None,
+        };
+    }
+
+    for (re, build) in natural_patterns() {
+        if let Some(caps) = re.captures(message) {
+            let arg = clean_argument(&caps[1]);
+            if !arg.is_empty() {
+                return Some(build(arg));
+            }
+        }
+    }
+    None
+}
+
+pub async fn execute(
+    command: MemoryCommand,
+    memory_service: &MemoryService,
+    session_id: Option<&str>,
+) -> Result<ActionResponse, String> {
+    let (data, metadata) = match command {
+        MemoryCommand::Remember(statement) => remember(&statement, memory_service, session_id).await?,
+        MemoryCommand::Forget { target, confirmed } => forget(&target, confirmed, memory_service).await?,
+        MemoryCommand::Recall(entity) => recall(&entity, memory_service).await?,
+    };
+
+    Ok(ActionResponse {
+        request_id: Uuid::new_v4(),
+        status: "success".to_string(),
+        code: 0,
+        result: Some(ActionResult {
+            output_type: "text".to_string(),
+            data,
+            metadata: Some(metadata),
+        }),
+        error: None,
+    })
+}
+
+// "#word" tokens in a remembered statement become tags.
+fn hashtags(statement: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in statement.split_whitespace() {
+        let tag = word.trim_start_matches('#').trim_end_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        if word.starts_with('#') && !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+async fn remember(
+    statement: &str,
+    memory_service: &MemoryService,
+    session_id: Option<&str>,
+) -> Result<(String, serde_json::Value), String> {
+    let triples = extract_rule_based(statement);
+    let mut stored = Vec::new();
+
+    for triple in &triples {
+        let provenance = FactProvenance {
+            trace_id: None,
+            origin: "user_command".to_string(),
+            status: FactStatus::Confirmed,
+        };
+        let id = memory_service
+            .add_knowledge_triple(&triple.subject, &triple.predicate, &triple.object, provenance)
+            .await?;
+        // An explicit command overrides an earlier rejection or pending review.
+        memory_service.set_triple_status(id, FactStatus::Confirmed).await?;
+        stored.push(format!("{} {} {}", triple.subject, triple.predicate, triple.object));
+    }
+
+    let metadata = MemoryMetadata {
+        source: MemorySource::Chat,
+        tool: None,
+        session_id: session_id.map(str::to_string),
+        tags: hashtags(statement),
+    };
+    memory_service.store_semantic_memory(statement, &metadata).await?;
+
+    let data = if stored.is_empty() {
+        format!("Remembered as a note: \"{}\"", statement)
+    } else {
+        format!("Remembered:\n{}", stored.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n"))
+    };
+    Ok((data, json!({"command": "remember", "facts": stored, "note": statement})))
+}
+
+// Notes match when they contain every word of the target; a chunked
+// document is forgotten whole. Nothing is deleted while more notes match
+// than MAX_UNCONFIRMED_FORGET and the command wasn't confirmed.
+async fn forget(target: &str, confirmed: bool, memory_service: &MemoryService) -> Result<(String, serde_json::Value), String> {
+    let notes = memory_service.find_semantic_memories(target).await?;
+    if notes.len() > MAX_UNCONFIRMED_FORGET && !confirmed {
+        let listed: Vec<String> = notes.iter().take(MAX_LISTED_NOTES).map(|(_, text)| preview(text)).collect();
+        let mut data = format!(
+            "\"{}\" matches {} notes, so nothing was forgotten yet. Send \"/forget! {}\" to forget them all:",
+            target,
+            notes.len(),
+            target
+        );
+        for note in &listed {
+            data.push_str(&format!("\n- {}", note));
+        }
+        if notes.len() > listed.len() {
+            data.push_str(&format!("\n- ... and {} more", notes.len() - listed.len()));
+        }
+        return Ok((
+            data,
+            json!({"command": "forget", "target": target, "confirm_required": true, "notes_matched": notes.len(), "notes": listed}),
+        ));
+    }
+
+    // "forget that X is Y" removes one fact; anything else forgets an entity.
+    let triples = extract_rule_based(target);
+    let mut facts_removed = 0;
+    if triples.is_empty() {
+        facts_removed += memory_service.delete_triples_about(target).await?;
+    } else {
+        for triple in &triples {
+            facts_removed += memory_service
+                .delete_knowledge_triple(&triple.subject, &triple.predicate, &triple.object)
+                .await?;
+        }
+    }
+
+    for (id, _) in &notes {
+        memory_service.delete_semantic_document(id).await?;
+    }
+
+    let data = format!(
+        "Forgot \"{}\": removed {} fact(s) and {} note(s).",
+        target,
+        facts_removed,
+        notes.len()
+    );
+    Ok((data, json!({"command": "forget", "target": target, "facts_removed": facts_removed, "notes_removed": notes.len()})))
+}
+
+// First line of a note, shortened for listing.
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+async fn recall(entity: &str, memory_service: &MemoryService) -> Result<(String, serde_json::Value), String> {
+    let triples = memory_service.triples_about(entity).await?;
+    let mut notes: Vec<String> = memory_service
+        .find_semantic_memories(entity)
+        .await?
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect();
+    notes.truncate(MAX_RECALLED_NOTES);
+
+    if triples.is_empty() && notes.is_empty() {
+        return Ok((
+            format!("I don't remember anything about \"{}\".", entity),
+            json!({"command": "recall", "entity": entity, "facts": [], "notes": []}),
+        ));
+    }
+
+    let mut data = format!("What I remember about \"{}\":", entity);
+    for triple in &triples {
+        let marker = if triple.status == FactStatus::Unreviewed.as_str() { " (unreviewed)" } else { "" };
+        data.push_str(&format!("\n- {} {} {}{}", triple.subject, triple.predicate, triple.object, marker));
+    }
+    for note in &notes {
+        data.push_str(&format!("\n- Note: {}", note));
+    }
+
+    Ok((data, json!({"command": "recall", "entity": entity, "facts": triples, "notes": notes})))
+}
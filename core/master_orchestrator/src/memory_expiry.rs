@@ -0,0 +1,32 @@
+use crate::memory_service::{MemoryService, MemorySource};
+use shared_types::AppConfig;
+use std::sync::Arc;
+
+// Periodically forgets semantic memories whose source has a TTL in
+// [memory.ttl_days] and that haven't been seen for that long.
+
+pub fn spawn_expiry(memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    let config = &app_config.memory;
+    if config.ttl_days.is_empty() || config.expiry_interval_minutes == 0 {
+        return;
+    }
+    for source in config.ttl_days.keys() {
+        if MemorySource::parse(source).is_none() {
+            eprintln!("[memory.ttl_days] names unknown source '{}'", source);
+        }
+    }
+    let ttl_days = config.ttl_days.clone();
+    let interval = std::time::Duration::from_secs(config.expiry_interval_minutes * 60);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match memory_service.expire_semantic_memories(&ttl_days).await {
+                Ok(0) => {}
+                Ok(removed) => println!("Expired {} semantic memories", removed),
+                Err(e) => eprintln!("Memory expiry failed: {}", e),
+            }
+        }
+    });
+}
@@ -1,12 +1,22 @@
-use rusqlite::{params, Connection};
-use shared_types::{ActionRequest, ActionResponse};
-use std::sync::{Arc, Mutex};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection};
+use shared_types::{ActionRequest, ActionResponse, ActionSpec, MemoryConfig, RetrievalConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
 use tokio::task;
 use std::fs;
 use std::path::Path;
-use std::io::Write;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use crate::context_builder::ContextReport;
+use crate::router::RoutingOutcome;
+use crate::scheduler::{from_timestamp, to_timestamp, CatchUpPolicy, ScheduledTask};
+use crate::workflow::WorkflowRun;
+use crate::goals::{Goal, GoalActivity, GoalNote, GoalStatus};
+use crate::quantize::Quantization;
+use crate::vector_index::VectorIndex;
+use crate::embedding::{self, EmbedderId, StoredVector};
+use crate::embedder::{embed_one, Embedder};
+use crate::chunker;
+use crate::entity_extractor::is_stopword;
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -18,21 +28,359 @@ pub struct AgentConfig {
     pub description: String,
 }
 
+// Extra trace columns recorded alongside the request/response pair.
+#[derive(Debug, Clone, Default)]
+pub struct TraceMeta {
+    pub context_report: Option<ContextReport>,
+    pub session_id: Option<String>,
+    pub schedule_id: Option<i64>,
+    pub trigger_name: Option<String>,
+    // Self-correction: the attempt this one retries, and its 1-based number.
+    pub parent_trace_id: Option<String>,
+    pub attempt: Option<i64>,
+    pub routing: Option<RoutingOutcome>,
+    pub workflow_run_id: Option<String>,
+    pub workflow_step: Option<String>,
+    // Pipe syntax: shared id for the chain and the 1-based segment number.
+    pub pipeline_id: Option<String>,
+    pub pipeline_step: Option<i64>,
+    // Wall-clock duration of the agent call.
+    pub latency_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CallStats {
+    pub samples: usize,
+    pub mean_latency_ms: u64,
+    pub p90_latency_ms: u64,
+    pub mean_output_chars: u64,
+}
+
+impl CallStats {
+    fn from_samples(mut latencies: Vec<u64>, total_output_chars: u64) -> Self {
+        if latencies.is_empty() {
+            return Self::default();
+        }
+        latencies.sort_unstable();
+        let n = latencies.len();
+        Self {
+            samples: n,
+            mean_latency_ms: latencies.iter().sum::<u64>() / n as u64,
+            p90_latency_ms: latencies[((n as f64 * 0.9).ceil() as usize).clamp(1, n) - 1],
+            mean_output_chars: total_output_chars / n as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TraceSummary {
+    pub trace_id: String,
+    pub timestamp: String,
+    pub response: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct KgFact {
+    pub id: i64,
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+    pub score: f32,
+    // 0 for a direct entity match, 1 for a one-hop neighbour.
+    pub hops: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactStatus {
+    Confirmed,
+    Unreviewed,
+    Rejected,
+}
+
+impl FactStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FactStatus::Confirmed => "confirmed",
+            FactStatus::Unreviewed => "unreviewed",
+            FactStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "confirmed" => Some(FactStatus::Confirmed),
+            "unreviewed" => Some(FactStatus::Unreviewed),
+            "rejected" => Some(FactStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+// Where a triple came from: "manual", "user_message" or "agent_result".
+#[derive(Debug, Clone)]
+pub struct FactProvenance {
+    pub trace_id: Option<String>,
+    pub origin: String,
+    pub status: FactStatus,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct KgTriple {
+    pub id: i64,
+    pub subject: String,
+    pub predicate: String,
+    pub object: String,
+    pub source_trace_id: Option<String>,
+    pub origin: String,
+    pub status: String,
+}
+
+impl std::fmt::Display for KgFact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.subject, self.predicate, self.object)
+    }
+}
+
+// Semantic memory layout. Memories live in the `semantic_memory` table; the
+// flat-file directories are only read once, by the migration.
+const LEGACY_TEXT_DIR: &str = "./data/memory/text";
+const LEGACY_VECTOR_DIR: &str = "./data/memory/vectors";
+const MIGRATED_DIR: &str = "./data/memory/migrated";
+const INDEX_PATH: &str = "./data/memory/index.json";
+// The index file is rewritten after this many changes; anything newer is
+// recovered from the database at startup.
+const INDEX_SAVE_EVERY: usize = 16;
+// Rebuild instead of saving once this share of index nodes are deletions.
+const INDEX_MAX_DELETED: f32 = 0.3;
+// Nearest memories checked for a near-duplicate on insert.
+const DEDUP_CANDIDATES: usize = 5;
+// A reindex lock whose holder hasn't checked in for this long was left
+// behind by a process that died, and may be taken over.
+const REINDEX_LOCK_STALE_SECONDS: i64 = 300;
+
+// How a semantic memory was ranked: its place and score in each ranking
+// (1-based; None when absent from it), the share of the query's words it
+// contains and its fused RRF score.
+#[derive(serde::Serialize, Debug, Clone, Default)]
+pub struct ScoreBreakdown {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_rank: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lexical_rank: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub term_overlap: Option<f32>,
+    pub rrf: f32,
+    // Score before recency and importance weighting.
+    pub relevance: f32,
+    pub recency: f32,
+    pub importance: f32,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct SemanticHit {
+    #[serde(flatten)]
+    pub memory: SemanticMemory,
+    pub score: f32,
+    pub breakdown: ScoreBreakdown,
+}
+
+// Where a semantic memory came from.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MemorySource {
+    // An agent call logged by the planner
+    #[default]
+    Trace,
+    // /remember in chat
+    Chat,
+    // Ingested vault notes and commits, not traces of the agents that
+    // handle them (those stay Trace, with the agent in `tool`)
+    VaultNote,
+    GitCommit,
+    // A file indexed by a watcher
+    File,
+}
+
+impl MemorySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemorySource::Trace => "trace",
+            MemorySource::Chat => "chat",
+            MemorySource::VaultNote => "vault_note",
+            MemorySource::GitCommit => "git_commit",
+            MemorySource::File => "file",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trace" => Some(MemorySource::Trace),
+            "chat" => Some(MemorySource::Chat),
+            "vault_note" => Some(MemorySource::VaultNote),
+            "git_commit" => Some(MemorySource::GitCommit),
+            "file" => Some(MemorySource::File),
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct MemoryMetadata {
+    pub source: MemorySource,
+    pub tool: Option<String>,
+    pub session_id: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct SemanticMemory {
+    pub id: String,
+    pub text: String,
+    #[serde(flatten)]
+    pub metadata: MemoryMetadata,
+    pub created_at: String,
+    // Last stored or merged into.
+    pub updated_at: String,
+    pub hit_count: i64,
+    pub importance: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<ChunkRef>,
+}
+
+// Where a chunk sits in the text it was cut from.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ChunkRef {
+    pub parent_id: String,
+    pub index: i64,
+    // Byte range within the parent text
+    pub start: i64,
+    pub end: i64,
+}
+
+// Restricts semantic retrieval. Every set condition must hold; `tags`
+// requires all of the listed tags, `sources` any of the listed sources.
+// Times are RFC 3339 and bound `created_at`.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MemoryFilter {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub sources: Vec<MemorySource>,
+    pub tags: Vec<String>,
+    pub session_id: Option<String>,
+    // Chunks of one document.
+    pub parent_id: Option<String>,
+    // Overrides [retrieval] min_score: the least cosine similarity a vector
+    // hit, or BM25 score a lexical hit, needs to be ranked at all.
+    pub min_score: Option<f32>,
+}
+
+impl MemoryFilter {
+    // Whether any metadata condition is set.
+    pub fn restricts(&self) -> bool {
+        self.since.is_some()
+            || self.until.is_some()
+            || !self.sources.is_empty()
+            || !self.tags.is_empty()
+            || self.session_id.is_some()
+            || self.parent_id.is_some()
+    }
+
+    // SQL condition over semantic_memory columns, with its parameters.
+    fn to_sql(&self) -> Result<(String, Vec<Value>), String> {
+        let mut clauses = vec!["1 = 1".to_string()];
+        let mut values = Vec::new();
+        for (bound, op) in [(&self.since, ">="), (&self.until, "<=")] {
+            if let Some(time) = bound {
+                let time = from_timestamp(time).ok_or_else(|| format!("Invalid timestamp '{}' (expected RFC 3339)", time))?;
+                clauses.push(format!("created_at {} ?", op));
+                values.push(Value::Text(to_timestamp(time)));
+            }
+        }
+        if !self.sources.is_empty() {
+            clauses.push(format!("source IN ({})", vec!["?"; self.sources.len()].join(", ")));
+            values.extend(self.sources.iter().map(|s| Value::Text(s.as_str().to_string())));
+        }
+        for tag in &self.tags {
+            clauses.push("EXISTS (SELECT 1 FROM json_each(semantic_memory.tags) WHERE value = ?)".to_string());
+            values.push(Value::Text(tag.clone()));
+        }
+        if let Some(session_id) = &self.session_id {
+            clauses.push("session_id = ?".to_string());
+            values.push(Value::Text(session_id.clone()));
+        }
+        if let Some(parent_id) = &self.parent_id {
+            clauses.push("parent_id = ?".to_string());
+            values.push(Value::Text(parent_id.clone()));
+        }
+        Ok((clauses.join(" AND "), values))
+    }
+}
+
+// One re-embedding run. `staged` counts vectors written so far; they
+// replace the live ones only when the job completes.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct ReindexJob {
+    pub generation: i64,
+    pub embedder: String,
+    // "running", "complete" or "abandoned"
+    pub status: String,
+    pub total: i64,
+    pub staged: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct IndexState {
+    index: VectorIndex,
+    unsaved: usize,
+    // Stored vectors from an older version of the embedder
+    outdated: usize,
+}
+
 #[derive(Clone)]
 pub struct MemoryService {
     conn: Arc<Mutex<Connection>>,
+    index: Arc<Mutex<IndexState>>,
+    // Swapped for a refreshed snapshot when a reindex finishes
+    embedder: Arc<RwLock<Arc<dyn Embedder>>>,
+    policy: MemoryConfig,
+    quantization: Quantization,
 }
 
 impl MemoryService {
-    pub fn new(db_path: &str) -> Result<Self, String> {
+    pub fn new(db_path: &str, embedder: Arc<dyn Embedder>, policy: MemoryConfig) -> Result<Self, String> {
         let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        let id = embedder.id();
+        let quantization = Quantization::parse(&policy.quantization.mode)?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            index: Arc::new(Mutex::new(IndexState {
+                index: VectorIndex::new(&id.to_string(), id.dim, quantization),
+                unsaved: 0,
+                outdated: 0,
+            })),
+            embedder: Arc::new(RwLock::new(embedder)),
+            policy,
+            quantization,
         })
     }
 
     pub async fn init_gai_memory(&self) -> Result<(), String> {
         let conn = self.conn.clone();
+        let trace_importance = self.policy.importance.get(MemorySource::Trace.as_str()).copied().unwrap_or(0.5);
         task::spawn_blocking(move || {
             let conn = conn.lock().unwrap();
             
@@ -59,6 +407,106 @@ impl MemoryService {
                 [],
             ).map_err(|e| e.to_string())?;
 
+            ensure_column(&conn, "action_trace_log", "context_report_json", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "schedule_id", "INTEGER")?;
+            ensure_column(&conn, "action_trace_log", "trigger_name", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "parent_trace_id", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "attempt", "INTEGER")?;
+            ensure_column(&conn, "action_trace_log", "routing_json", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "workflow_run_id", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "workflow_step", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "pipeline_id", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "pipeline_step", "INTEGER")?;
+            ensure_column(&conn, "action_trace_log", "session_id", "TEXT")?;
+            ensure_column(&conn, "action_trace_log", "latency_ms", "INTEGER")?;
+
+            // Workflow runs; per-step traces link back through workflow_run_id
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS workflow_runs (
+                    run_id TEXT PRIMARY KEY,
+                    workflow TEXT NOT NULL,
+                    inputs_json TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    steps_json TEXT NOT NULL,
+                    output TEXT,
+                    started_at TEXT NOT NULL,
+                    finished_at TEXT
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
+            // Scheduled and recurring tasks
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS scheduled_tasks (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL UNIQUE,
+                    cron TEXT NOT NULL,
+                    message TEXT,
+                    action_json TEXT,
+                    session_id TEXT,
+                    catch_up TEXT NOT NULL,
+                    enabled INTEGER NOT NULL,
+                    last_run_at TEXT,
+                    next_run_at TEXT
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
+            // Long-running goals, their notes and linked traces
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS goals (
+                    id INTEGER PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT NOT NULL DEFAULT '',
+                    status TEXT NOT NULL,
+                    due TEXT,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    last_check_in_at TEXT
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS goal_notes (
+                    id INTEGER PRIMARY KEY,
+                    goal_id INTEGER NOT NULL,
+                    kind TEXT NOT NULL,
+                    text TEXT NOT NULL,
+                    created_at TEXT NOT NULL
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS goal_traces (
+                    goal_id INTEGER NOT NULL,
+                    trace_id TEXT NOT NULL,
+                    linked_at TEXT NOT NULL,
+                    PRIMARY KEY (goal_id, trace_id)
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
+            // Session history (short-term conversational memory)
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS session_history (
+                    id INTEGER PRIMARY KEY,
+                    session_id TEXT NOT NULL,
+                    role TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    timestamp TEXT DEFAULT CURRENT_TIMESTAMP
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
+            // Per-session settings (prompt template selection)
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS session_settings (
+                    session_id TEXT PRIMARY KEY,
+                    template TEXT
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
             // Layer 1: Knowledge Graph (Structured Memory)
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS knowledge_graph (
@@ -71,14 +519,120 @@ impl MemoryService {
                 [],
             ).map_err(|e| e.to_string())?;
 
+            // Provenance and review state for extracted facts
+            ensure_column(&conn, "knowledge_graph", "source_trace_id", "TEXT")?;
+            ensure_column(&conn, "knowledge_graph", "origin", "TEXT NOT NULL DEFAULT 'manual'")?;
+            ensure_column(&conn, "knowledge_graph", "status", "TEXT NOT NULL DEFAULT 'confirmed'")?;
+
+            // Reindex generations: vectors are staged per generation and
+            // swapped into semantic_memory in one transaction when complete
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS reindex_jobs (
+                    generation INTEGER PRIMARY KEY,
+                    embedder TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    total INTEGER NOT NULL,
+                    started_at TEXT NOT NULL,
+                    finished_at TEXT,
+                    error TEXT
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS reindex_staging (
+                    generation INTEGER NOT NULL,
+                    id TEXT NOT NULL,
+                    vector BLOB NOT NULL,
+                    checksum TEXT NOT NULL,
+                    PRIMARY KEY (generation, id)
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            ensure_column(&conn, "reindex_jobs", "embedder_state", "TEXT")?;
+            // Corpus statistics snapshot each stateful embedder's stored
+            // vectors were embedded under, by embedder name
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS embedder_state (
+                    embedder TEXT PRIMARY KEY,
+                    state TEXT NOT NULL
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            // At most one reindex at a time, across the server and the CLI.
+            // The holder refreshes heartbeat_at (unix seconds) every batch.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS reindex_lock (
+                    id INTEGER PRIMARY KEY CHECK (id = 1),
+                    owner TEXT NOT NULL,
+                    heartbeat_at INTEGER NOT NULL
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+
+            // Layer 2: Semantic Memory (text + little-endian f32 vector)
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS semantic_memory (
+                    id TEXT PRIMARY KEY,
+                    text TEXT NOT NULL,
+                    vector BLOB NOT NULL,
+                    created_at TEXT NOT NULL,
+                    checksum TEXT NOT NULL
+                )",
+                [],
+            ).map_err(|e| e.to_string())?;
+            // Metadata; rows from before it existed count as traces
+            ensure_column(&conn, "semantic_memory", "source", "TEXT NOT NULL DEFAULT 'trace'")?;
+            ensure_column(&conn, "semantic_memory", "tool", "TEXT")?;
+            ensure_column(&conn, "semantic_memory", "session_id", "TEXT")?;
+            ensure_column(&conn, "semantic_memory", "tags", "TEXT NOT NULL DEFAULT '[]'")?;
+            ensure_column(&conn, "semantic_memory", "updated_at", "TEXT")?;
+            ensure_column(&conn, "semantic_memory", "hit_count", "INTEGER NOT NULL DEFAULT 1")?;
+            ensure_column(&conn, "semantic_memory", "importance", "REAL NOT NULL DEFAULT 0.3")?;
+            // Chunks of a long text share a parent id and record their byte
+            // range within it
+            ensure_column(&conn, "semantic_memory", "parent_id", "TEXT")?;
+            ensure_column(&conn, "semantic_memory", "chunk_index", "INTEGER")?;
+            ensure_column(&conn, "semantic_memory", "chunk_start", "INTEGER")?;
+            ensure_column(&conn, "semantic_memory", "chunk_end", "INTEGER")?;
+            conn.execute_batch(
+                "UPDATE semantic_memory SET updated_at = created_at WHERE updated_at IS NULL;
+                 CREATE INDEX IF NOT EXISTS idx_semantic_memory_created ON semantic_memory (created_at);
+                 CREATE INDEX IF NOT EXISTS idx_semantic_memory_source ON semantic_memory (source, updated_at);
+                 CREATE INDEX IF NOT EXISTS idx_semantic_memory_parent ON semantic_memory (parent_id, chunk_start);",
+            ).map_err(|e| e.to_string())?;
+            backfill_trace_tools(&conn)?;
+            relabel_agent_traces(&conn, trace_importance)?;
+
+            // Full-text index over memory texts for BM25 ranking, kept in
+            // step with semantic_memory by triggers
+            conn.execute_batch(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS semantic_memory_fts
+                    USING fts5(id UNINDEXED, text, tokenize = 'porter unicode61');
+                 CREATE TRIGGER IF NOT EXISTS semantic_memory_fts_insert AFTER INSERT ON semantic_memory BEGIN
+                    INSERT INTO semantic_memory_fts (id, text) VALUES (new.id, new.text);
+                 END;
+                 CREATE TRIGGER IF NOT EXISTS semantic_memory_fts_delete AFTER DELETE ON semantic_memory BEGIN
+                    DELETE FROM semantic_memory_fts WHERE id = old.id;
+                 END;
+                 CREATE TRIGGER IF NOT EXISTS semantic_memory_fts_update AFTER UPDATE OF text ON semantic_memory BEGIN
+                    DELETE FROM semantic_memory_fts WHERE id = old.id;
+                    INSERT INTO semantic_memory_fts (id, text) VALUES (new.id, new.text);
+                 END;
+                 INSERT INTO semantic_memory_fts (id, text)
+                    SELECT id, text FROM semantic_memory
+                    WHERE id NOT IN (SELECT id FROM semantic_memory_fts);",
+            ).map_err(|e| e.to_string())?;
+
             Ok::<(), String>(())
         })
         .await
         .map_err(|e| e.to_string())??;
 
-        // Layer 2: Semantic Memory (Flat-File Directories)
-        fs::create_dir_all("./data/memory/text").map_err(|e| e.to_string())?;
-        fs::create_dir_all("./data/memory/vectors").map_err(|e| e.to_string())?;
+        // Layer 2: Semantic Memory (SQLite rows + ANN index)
+        self.prime_embedder().await?;
+        self.restore_embedder_state().await?;
+        self.migrate_flat_file_memories().await?;
+        self.load_semantic_index(false).await?;
 
         Ok(())
     }
@@ -87,26 +641,51 @@ impl MemoryService {
         &self,
         request: &ActionRequest,
         response: &ActionResponse,
+        meta: &TraceMeta,
     ) -> Result<(), String> {
         let conn = self.conn.clone();
         let request_json = serde_json::to_string(request).unwrap_or_default();
         let response_json = serde_json::to_string(response).unwrap_or_default();
+        let context_report_json = meta.context_report.as_ref().and_then(|r| serde_json::to_string(r).ok());
+        let schedule_id = meta.schedule_id;
+        let trigger_name = meta.trigger_name.clone();
+        let parent_trace_id = meta.parent_trace_id.clone();
+        let attempt = meta.attempt;
+        let routing_json = meta.routing.as_ref().and_then(|r| serde_json::to_string(r).ok());
+        let workflow_run_id = meta.workflow_run_id.clone();
+        let workflow_step = meta.workflow_step.clone();
+        let pipeline_id = meta.pipeline_id.clone();
+        let pipeline_step = meta.pipeline_step;
+        let latency_ms = meta.latency_ms;
+        let session_id = meta.session_id.clone();
         let trace_id = request.request_id.to_string();
 
-        // Also store as semantic memory for retrieval!
         let semantic_text = format!("Action: {} Tool: {} Prompt: {}", request.action, request.tool, request.payload.0);
-        self.store_semantic_memory(&semantic_text).await?;
+        let metadata = MemoryMetadata {
+            source: MemorySource::Trace,
+            tool: Some(request.tool.clone()),
+            session_id: meta.session_id.clone(),
+            tags: vec![request.action.clone()],
+        };
 
         task::spawn_blocking(move || {
             let conn = conn.lock().unwrap();
             conn.execute(
-                "INSERT INTO action_trace_log (trace_id, request_json, response_json) VALUES (?1, ?2, ?3)",
-                params![trace_id, request_json, response_json],
+                "INSERT INTO action_trace_log (trace_id, request_json, response_json, context_report_json, schedule_id, trigger_name, parent_trace_id, attempt, routing_json, workflow_run_id, workflow_step, pipeline_id, pipeline_step, latency_ms, session_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                params![trace_id, request_json, response_json, context_report_json, schedule_id, trigger_name, parent_trace_id, attempt, routing_json, workflow_run_id, workflow_step, pipeline_id, pipeline_step, latency_ms, session_id],
             ).map_err(|e| e.to_string())?;
             Ok::<(), String>(())
         })
         .await
-        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())??;
+
+        // Also store as semantic memory for retrieval. The trace is already
+        // written, so a failing embedder only costs its searchability.
+        if let Err(e) = self.store_semantic_memory(&semantic_text, &metadata).await {
+            eprintln!("Trace {} logged but not indexed as a memory: {}", request.request_id, e);
+        }
+        Ok(())
     }
 
     pub async fn register_agent(
@@ -165,19 +744,19 @@ impl MemoryService {
         .map_err(|e| e.to_string())?
     }
 
-    // --- Layer 1: Structured Memory (KG) ---
+    // --- Session History ---
 
-    pub async fn add_knowledge_triple(&self, subject: &str, predicate: &str, object: &str) -> Result<(), String> {
+    pub async fn append_session_message(&self, session_id: &str, role: &str, content: &str) -> Result<(), String> {
         let conn = self.conn.clone();
-        let s = subject.to_string();
-        let p = predicate.to_string();
-        let o = object.to_string();
+        let session_id = session_id.to_string();
+        let role = role.to_string();
+        let content = content.to_string();
 
         task::spawn_blocking(move || {
             let conn = conn.lock().unwrap();
             conn.execute(
-                "INSERT OR IGNORE INTO knowledge_graph (subject, predicate, object) VALUES (?1, ?2, ?3)",
-                params![s, p, o],
+                "INSERT INTO session_history (session_id, role, content) VALUES (?1, ?2, ?3)",
+                params![session_id, role, content],
             ).map_err(|e| e.to_string())?;
             Ok::<(), String>(())
         })
@@ -185,144 +764,2362 @@ impl MemoryService {
         .map_err(|e| e.to_string())?
     }
 
-    pub async fn retrieve_structured_context(&self, query: &str) -> Result<Vec<String>, String> {
+    // Returns the most recent `limit` messages for a session, oldest first.
+    pub async fn get_session_history(&self, session_id: &str, limit: usize) -> Result<Vec<SessionMessage>, String> {
         let conn = self.conn.clone();
-        let q = format!("%{}%", query); // Simple LIKE query for now
+        let session_id = session_id.to_string();
 
         task::spawn_blocking(move || {
             let conn = conn.lock().unwrap();
             let mut stmt = conn.prepare(
-                "SELECT subject, predicate, object FROM knowledge_graph 
-                 WHERE subject LIKE ?1 OR object LIKE ?1 LIMIT 10"
+                "SELECT role, content FROM session_history
+                 WHERE session_id = ?1 ORDER BY id DESC LIMIT ?2"
             ).map_err(|e| e.to_string())?;
 
-            let rows = stmt.query_map(params![q], |row| {
-                let s: String = row.get(0)?;
-                let p: String = row.get(1)?;
-                let o: String = row.get(2)?;
-                Ok(format!("{} {} {}", s, p, o))
+            let rows = stmt.query_map(params![session_id, limit as i64], |row| {
+                Ok(SessionMessage {
+                    role: row.get(0)?,
+                    content: row.get(1)?,
+                })
             }).map_err(|e| e.to_string())?;
 
-            let mut results = Vec::new();
+            let mut messages = Vec::new();
             for row in rows {
-                results.push(row.map_err(|e| e.to_string())?);
+                messages.push(row.map_err(|e| e.to_string())?);
             }
-            Ok::<Vec<String>, String>(results)
+            messages.reverse();
+            Ok::<Vec<SessionMessage>, String>(messages)
         })
         .await
         .map_err(|e| e.to_string())?
     }
 
-    // --- Layer 2: Semantic Memory (Flat-File Vector) ---
+    pub async fn set_session_template(&self, session_id: &str, template: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let session_id = session_id.to_string();
+        let template = template.to_string();
 
-    // 200-Year Longevity: Pure Rust "SimpleHashEmbedding"
-    // Maps words to a fixed-size vector (128 dimensions) using hashing.
-    // No external dependencies, no models to download, no C++ runtimes.
-    fn generate_simple_embedding(text: &str) -> Vec<f32> {
-        const DIM: usize = 128;
-        let mut vector = vec![0.0; DIM];
-        let words = text.split_whitespace();
-        
-        for word in words {
-            let mut hasher = DefaultHasher::new();
-            word.hash(&mut hasher);
-            let hash = hasher.finish();
-            let index = (hash as usize) % DIM;
-            vector[index] += 1.0;
-        }
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO session_settings (session_id, template) VALUES (?1, ?2)
+                 ON CONFLICT(session_id) DO UPDATE SET template = excluded.template",
+                params![session_id, template],
+            ).map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn get_session_template(&self, session_id: &str) -> Result<Option<String>, String> {
+        let conn = self.conn.clone();
+        let session_id = session_id.to_string();
 
-        // Normalize
-        let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
-        if magnitude > 0.0 {
-            for x in &mut vector {
-                *x /= magnitude;
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT template FROM session_settings WHERE session_id = ?1")
+                .map_err(|e| e.to_string())?;
+            let mut rows = stmt.query(params![session_id]).map_err(|e| e.to_string())?;
+            match rows.next().map_err(|e| e.to_string())? {
+                Some(row) => row.get(0).map_err(|e| e.to_string()),
+                None => Ok(None),
             }
-        }
-        
-        vector
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 
-    pub async fn store_semantic_memory(&self, text: &str) -> Result<(), String> {
-        let text_content = text.to_string();
+    // --- Scheduled Tasks ---
 
-        // 1. Generate Embedding (Pure Rust)
-        let embedding = Self::generate_simple_embedding(&text_content);
+    pub async fn create_scheduled_task(&self, task: &ScheduledTask) -> Result<i64, String> {
+        let conn = self.conn.clone();
+        let task = task.clone();
+        let action_json = task.action.as_ref().map(|a| serde_json::to_string(a).unwrap_or_default());
 
-        // 2. Save to Flat Files
-        let id = uuid::Uuid::new_v4().to_string();
-        
-        // Save Text
-        let text_path = format!("./data/memory/text/{}.txt", id);
-        fs::write(&text_path, text).map_err(|e| e.to_string())?;
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO scheduled_tasks (name, cron, message, action_json, session_id, catch_up, enabled, last_run_at, next_run_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    task.name,
+                    task.cron,
+                    task.message,
+                    action_json,
+                    task.session_id,
+                    task.catch_up.as_str(),
+                    task.enabled,
+                    task.last_run_at,
+                    task.next_run_at
+                ],
+            ).map_err(|e| e.to_string())?;
+            Ok::<i64, String>(conn.last_insert_rowid())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
 
-        // Save Vector (Binary)
-        let vec_path = format!("./data/memory/vectors/{}.bin", id);
-        let mut file = fs::File::create(&vec_path).map_err(|e| e.to_string())?;
-        for &val in &embedding {
-            let bytes = val.to_le_bytes();
-            file.write_all(&bytes).map_err(|e| e.to_string())?;
-        }
+    // Returns false if no task has this id.
+    pub async fn update_scheduled_task(&self, task: &ScheduledTask) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        let task = task.clone();
+        let action_json = task.action.as_ref().map(|a| serde_json::to_string(a).unwrap_or_default());
 
-        Ok(())
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let changed = conn.execute(
+                "UPDATE scheduled_tasks SET name = ?2, cron = ?3, message = ?4, action_json = ?5, session_id = ?6,
+                    catch_up = ?7, enabled = ?8, next_run_at = ?9
+                 WHERE id = ?1",
+                params![
+                    task.id,
+                    task.name,
+                    task.cron,
+                    task.message,
+                    action_json,
+                    task.session_id,
+                    task.catch_up.as_str(),
+                    task.enabled,
+                    task.next_run_at
+                ],
+            ).map_err(|e| e.to_string())?;
+            Ok::<bool, String>(changed > 0)
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 
-    pub async fn retrieve_semantic_context(&self, query: &str, k: usize) -> Result<Vec<String>, String> {
-        let query_text = query.to_string();
-
-        // 1. Generate Query Embedding (Pure Rust)
-        let query_vec = Self::generate_simple_embedding(&query_text);
-
-        // 2. Scan Flat Files (Linear Search - 200 Year Simplicity)
-        let vec_dir = Path::new("./data/memory/vectors");
-        let mut scores: Vec<(String, f32)> = Vec::new();
-
-        if vec_dir.exists() {
-            for entry in fs::read_dir(vec_dir).map_err(|e| e.to_string())? {
-                let entry = entry.map_err(|e| e.to_string())?;
-                let path = entry.path();
-                if path.extension().and_then(|s| s.to_str()) == Some("bin") {
-                    let id = path.file_stem().unwrap().to_string_lossy().to_string();
-                    
-                    // Read vector
-                    let bytes = fs::read(&path).map_err(|e| e.to_string())?;
-                    let mut vector = Vec::new();
-                    for chunk in bytes.chunks(4) {
-                        if chunk.len() == 4 {
-                            let val = f32::from_le_bytes(chunk.try_into().unwrap());
-                            vector.push(val);
-                        }
-                    }
+    pub async fn delete_scheduled_task(&self, id: i64) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let changed = conn
+                .execute("DELETE FROM scheduled_tasks WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())?;
+            Ok::<bool, String>(changed > 0)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
 
-                    // Cosine Similarity
-                    let score = cosine_similarity(&query_vec, &vector);
-                    scores.push((id, score));
-                }
+    // All tasks, or only enabled tasks due at or before `due_before` (UTC RFC 3339).
+    pub async fn list_scheduled_tasks(&self, due_before: Option<String>) -> Result<Vec<ScheduledTask>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, name, cron, message, action_json, session_id, catch_up, enabled, last_run_at, next_run_at
+                 FROM scheduled_tasks
+                 WHERE ?1 IS NULL OR (enabled = 1 AND next_run_at IS NOT NULL AND next_run_at <= ?1)
+                 ORDER BY id"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![due_before], |row| {
+                let action_json: Option<String> = row.get(4)?;
+                let catch_up: String = row.get(6)?;
+                Ok(ScheduledTask {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    cron: row.get(2)?,
+                    message: row.get(3)?,
+                    action: action_json.and_then(|j| serde_json::from_str::<ActionSpec>(&j).ok()),
+                    session_id: row.get(5)?,
+                    catch_up: CatchUpPolicy::parse(&catch_up).unwrap_or_default(),
+                    enabled: row.get(7)?,
+                    last_run_at: row.get(8)?,
+                    next_run_at: row.get(9)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut tasks = Vec::new();
+            for row in rows {
+                tasks.push(row.map_err(|e| e.to_string())?);
             }
-        }
+            Ok::<Vec<ScheduledTask>, String>(tasks)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn record_schedule_run(&self, id: i64, last_run_at: Option<String>, next_run_at: Option<String>) -> Result<(), String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "UPDATE scheduled_tasks SET last_run_at = COALESCE(?2, last_run_at), next_run_at = ?3 WHERE id = ?1",
+                params![id, last_run_at, next_run_at],
+            ).map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Run history for a schedule, newest first.
+    pub async fn get_schedule_runs(&self, schedule_id: i64, limit: usize) -> Result<Vec<TraceSummary>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT trace_id, timestamp, response_json FROM action_trace_log
+                 WHERE schedule_id = ?1 ORDER BY timestamp DESC, rowid DESC LIMIT ?2"
+            ).map_err(|e| e.to_string())?;
 
-        // 3. Sort and Retrieve Text
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        let top_k = scores.into_iter().take(k).collect::<Vec<_>>();
+            let rows = stmt.query_map(params![schedule_id, limit as i64], |row| {
+                let response_json: String = row.get(2)?;
+                Ok(TraceSummary {
+                    trace_id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    response: serde_json::from_str(&response_json).unwrap_or(serde_json::Value::Null),
+                })
+            }).map_err(|e| e.to_string())?;
 
-        let mut results = Vec::new();
-        for (id, _score) in top_k {
-            let text_path = format!("./data/memory/text/{}.txt", id);
-            if let Ok(content) = fs::read_to_string(text_path) {
-                results.push(content);
+            let mut runs = Vec::new();
+            for row in rows {
+                runs.push(row.map_err(|e| e.to_string())?);
             }
-        }
+            Ok::<Vec<TraceSummary>, String>(runs)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
 
-        Ok(results)
+    // How many traces a schedule has; lets the scheduler tell whether a run
+    // traced anything.
+    pub async fn count_schedule_traces(&self, schedule_id: i64) -> Result<i64, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT COUNT(*) FROM action_trace_log WHERE schedule_id = ?1",
+                params![schedule_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
-}
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
+    // Latency and output size of recent successful-or-not calls to one
+    // agent action, newest `window` traces with a recorded latency.
+    pub async fn call_stats(&self, tool: &str, action: &str, window: usize) -> Result<CallStats, String> {
+        let conn = self.conn.clone();
+        let tool = tool.to_string();
+        let action = action.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT latency_ms, length(COALESCE(json_extract(response_json, '$.result.data'), ''))
+                 FROM action_trace_log
+                 WHERE latency_ms IS NOT NULL
+                   AND json_extract(request_json, '$.tool') = ?1
+                   AND json_extract(request_json, '$.action') = ?2
+                 ORDER BY timestamp DESC, rowid DESC LIMIT ?3"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![tool, action, window as i64], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            }).map_err(|e| e.to_string())?;
+
+            let mut latencies = Vec::new();
+            let mut output_chars = 0;
+            for row in rows {
+                let (latency, chars) = row.map_err(|e| e.to_string())?;
+                latencies.push(latency as u64);
+                output_chars += chars as u64;
+            }
+            Ok::<CallStats, String>(CallStats::from_samples(latencies, output_chars))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // --- Goals ---
+
+    pub async fn create_goal(&self, goal: &Goal) -> Result<i64, String> {
+        let conn = self.conn.clone();
+        let goal = goal.clone();
+        let now = to_timestamp(chrono::Local::now());
+
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO goals (title, description, status, due, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                params![goal.title, goal.description, goal.status.as_str(), goal.due, now],
+            ).map_err(|e| e.to_string())?;
+            Ok::<i64, String>(conn.last_insert_rowid())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Returns false if no goal has this id.
+    pub async fn update_goal(&self, goal: &Goal) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        let goal = goal.clone();
+        let now = to_timestamp(chrono::Local::now());
+
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let changed = conn.execute(
+                "UPDATE goals SET title = ?2, description = ?3, status = ?4, due = ?5, updated_at = ?6 WHERE id = ?1",
+                params![goal.id, goal.title, goal.description, goal.status.as_str(), goal.due, now],
+            ).map_err(|e| e.to_string())?;
+            Ok::<bool, String>(changed > 0)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn delete_goal(&self, id: i64) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute("DELETE FROM goal_notes WHERE goal_id = ?1", params![id]).map_err(|e| e.to_string())?;
+            conn.execute("DELETE FROM goal_traces WHERE goal_id = ?1", params![id]).map_err(|e| e.to_string())?;
+            let changed = conn
+                .execute("DELETE FROM goals WHERE id = ?1", params![id])
+                .map_err(|e| e.to_string())?;
+            Ok::<bool, String>(changed > 0)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // All goals, or only those with `status`, oldest first.
+    pub async fn list_goals(&self, status: Option<GoalStatus>) -> Result<Vec<Goal>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, title, description, status, due, created_at, updated_at, last_check_in_at
+                 FROM goals WHERE ?1 IS NULL OR status = ?1 ORDER BY id"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![status.map(|s| s.as_str())], |row| {
+                let status: String = row.get(3)?;
+                Ok(Goal {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    description: row.get(2)?,
+                    status: GoalStatus::parse(&status).unwrap_or_default(),
+                    due: row.get(4)?,
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                    last_check_in_at: row.get(7)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut goals = Vec::new();
+            for row in rows {
+                goals.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<Goal>, String>(goals)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn add_goal_note(&self, goal_id: i64, kind: &str, text: &str) -> Result<GoalNote, String> {
+        let conn = self.conn.clone();
+        let note = GoalNote {
+            id: 0,
+            goal_id,
+            kind: kind.to_string(),
+            text: text.to_string(),
+            created_at: to_timestamp(chrono::Local::now()),
+        };
+
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO goal_notes (goal_id, kind, text, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![note.goal_id, note.kind, note.text, note.created_at],
+            ).map_err(|e| e.to_string())?;
+            Ok::<GoalNote, String>(GoalNote {
+                id: conn.last_insert_rowid(),
+                ..note
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Newest first.
+    pub async fn get_goal_notes(&self, goal_id: i64, limit: usize) -> Result<Vec<GoalNote>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, goal_id, kind, text, created_at FROM goal_notes
+                 WHERE goal_id = ?1 ORDER BY id DESC LIMIT ?2"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![goal_id, limit as i64], |row| {
+                Ok(GoalNote {
+                    id: row.get(0)?,
+                    goal_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    text: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut notes = Vec::new();
+            for row in rows {
+                notes.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<GoalNote>, String>(notes)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn link_goal_trace(&self, goal_id: i64, trace_id: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let trace_id = trace_id.to_string();
+        let now = to_timestamp(chrono::Local::now());
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR IGNORE INTO goal_traces (goal_id, trace_id, linked_at) VALUES (?1, ?2, ?3)",
+                params![goal_id, trace_id, now],
+            ).map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Traces linked to a goal after `since` (UTC RFC 3339), newest first.
+    pub async fn goal_activity(&self, goal_id: i64, since: Option<&str>, limit: usize) -> Result<Vec<GoalActivity>, String> {
+        let conn = self.conn.clone();
+        let since = since.map(|s| s.to_string());
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT t.trace_id, g.linked_at,
+                        json_extract(t.request_json, '$.tool'),
+                        json_extract(t.request_json, '$.action'),
+                        json_extract(t.request_json, '$.payload.prompt'),
+                        COALESCE(json_extract(t.response_json, '$.result.data'), json_extract(t.response_json, '$.error'))
+                 FROM goal_traces g JOIN action_trace_log t ON t.trace_id = g.trace_id
+                 WHERE g.goal_id = ?1 AND (?2 IS NULL OR g.linked_at > ?2)
+                 ORDER BY g.linked_at DESC LIMIT ?3"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![goal_id, since, limit as i64], |row| {
+                Ok(GoalActivity {
+                    trace_id: row.get(0)?,
+                    linked_at: row.get(1)?,
+                    tool: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    action: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    prompt: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                    result: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut activity = Vec::new();
+            for row in rows {
+                activity.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<GoalActivity>, String>(activity)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn record_goal_check_in(&self, goal_id: i64, at: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let at = at.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute("UPDATE goals SET last_check_in_at = ?2 WHERE id = ?1", params![goal_id, at])
+                .map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // --- Workflow Runs ---
+
+    pub async fn record_workflow_run(&self, run: &WorkflowRun) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let inputs_json = serde_json::to_string(&run.inputs).map_err(|e| e.to_string())?;
+        let steps_json = serde_json::to_string(&run.steps).map_err(|e| e.to_string())?;
+        let run = run.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO workflow_runs (run_id, workflow, inputs_json, status, steps_json, output, started_at, finished_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT(run_id) DO UPDATE SET
+                    status = excluded.status,
+                    steps_json = excluded.steps_json,
+                    output = excluded.output,
+                    finished_at = excluded.finished_at",
+                params![run.run_id, run.workflow, inputs_json, run.status, steps_json, run.output, run.started_at, run.finished_at],
+            ).map_err(|e| e.to_string())?;
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Run history for a workflow, newest first.
+    pub async fn get_workflow_runs(&self, workflow: &str, limit: usize) -> Result<Vec<WorkflowRun>, String> {
+        let conn = self.conn.clone();
+        let workflow = workflow.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT run_id, workflow, inputs_json, status, steps_json, output, started_at, finished_at
+                 FROM workflow_runs WHERE workflow = ?1 ORDER BY started_at DESC, rowid DESC LIMIT ?2"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![workflow, limit as i64], |row| {
+                let inputs_json: String = row.get(2)?;
+                let steps_json: String = row.get(4)?;
+                Ok(WorkflowRun {
+                    run_id: row.get(0)?,
+                    workflow: row.get(1)?,
+                    inputs: serde_json::from_str(&inputs_json).unwrap_or_default(),
+                    status: row.get(3)?,
+                    steps: serde_json::from_str(&steps_json).unwrap_or_default(),
+                    output: row.get(5)?,
+                    started_at: row.get(6)?,
+                    finished_at: row.get(7)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut runs = Vec::new();
+            for row in rows {
+                runs.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<WorkflowRun>, String>(runs)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // --- Layer 1: Structured Memory (KG) ---
+
+    // Inserts a triple unless it already exists. Existing triples keep their
+    // status, so a rejected fact is not resurrected by re-extraction.
+    // Returns the row id of the new or existing triple.
+    pub async fn add_knowledge_triple(
+        &self,
+        subject: &str,
+        predicate: &str,
+        object: &str,
+        provenance: FactProvenance,
+    ) -> Result<i64, String> {
+        let conn = self.conn.clone();
+        let s = subject.to_string();
+        let p = predicate.to_string();
+        let o = object.to_string();
+
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR IGNORE INTO knowledge_graph (subject, predicate, object, source_trace_id, origin, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![s, p, o, provenance.trace_id, provenance.origin, provenance.status.as_str()],
+            ).map_err(|e| e.to_string())?;
+            conn.query_row(
+                "SELECT id FROM knowledge_graph WHERE subject = ?1 AND predicate = ?2 AND object = ?3",
+                params![s, p, o],
+                |row| row.get(0),
+            ).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn list_knowledge_triples(&self, status: Option<FactStatus>) -> Result<Vec<KgTriple>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, subject, predicate, object, source_trace_id, origin, status FROM knowledge_graph
+                 WHERE ?1 IS NULL OR status = ?1 ORDER BY id"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![status.map(|s| s.as_str())], |row| {
+                Ok(KgTriple {
+                    id: row.get(0)?,
+                    subject: row.get(1)?,
+                    predicate: row.get(2)?,
+                    object: row.get(3)?,
+                    source_trace_id: row.get(4)?,
+                    origin: row.get(5)?,
+                    status: row.get(6)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut triples = Vec::new();
+            for row in rows {
+                triples.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<KgTriple>, String>(triples)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Returns false if no triple has this id.
+    pub async fn set_triple_status(&self, id: i64, status: FactStatus) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let changed = conn.execute(
+                "UPDATE knowledge_graph SET status = ?1 WHERE id = ?2",
+                params![status.as_str(), id],
+            ).map_err(|e| e.to_string())?;
+            Ok::<bool, String>(changed > 0)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Non-rejected triples with the entity as subject or object.
+    pub async fn triples_about(&self, entity: &str) -> Result<Vec<KgTriple>, String> {
+        let conn = self.conn.clone();
+        let entity = entity.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, subject, predicate, object, source_trace_id, origin, status FROM knowledge_graph
+                 WHERE (subject = ?1 COLLATE NOCASE OR object = ?1 COLLATE NOCASE) AND status != 'rejected'
+                 ORDER BY id"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![entity], |row| {
+                Ok(KgTriple {
+                    id: row.get(0)?,
+                    subject: row.get(1)?,
+                    predicate: row.get(2)?,
+                    object: row.get(3)?,
+                    source_trace_id: row.get(4)?,
+                    origin: row.get(5)?,
+                    status: row.get(6)?,
+                })
+            }).map_err(|e| e.to_string())?;
+
+            let mut triples = Vec::new();
+            for row in rows {
+                triples.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<KgTriple>, String>(triples)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Deletes every triple mentioning the entity. Returns the number removed.
+    pub async fn delete_triples_about(&self, entity: &str) -> Result<usize, String> {
+        let conn = self.conn.clone();
+        let entity = entity.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "DELETE FROM knowledge_graph WHERE subject = ?1 COLLATE NOCASE OR object = ?1 COLLATE NOCASE",
+                params![entity],
+            ).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn delete_knowledge_triple(&self, subject: &str, predicate: &str, object: &str) -> Result<usize, String> {
+        let conn = self.conn.clone();
+        let s = subject.to_string();
+        let p = predicate.to_string();
+        let o = object.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "DELETE FROM knowledge_graph
+                 WHERE subject = ?1 COLLATE NOCASE AND predicate = ?2 COLLATE NOCASE AND object = ?3 COLLATE NOCASE",
+                params![s, p, o],
+            ).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // All distinct node names in the graph, used for entity matching. Facts
+    // are filtered as in retrieve_structured_context: rejected ones never
+    // count, unreviewed ones only if requested.
+    pub async fn get_kg_entities(&self, include_unreviewed: bool) -> Result<Vec<String>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT subject FROM knowledge_graph WHERE status = 'confirmed' OR (?1 AND status = 'unreviewed')
+                 UNION SELECT object FROM knowledge_graph WHERE status = 'confirmed' OR (?1 AND status = 'unreviewed')"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![include_unreviewed], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+
+            let mut entities = Vec::new();
+            for row in rows {
+                entities.push(row.map_err(|e| e.to_string())?);
+            }
+            Ok::<Vec<String>, String>(entities)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Facts touching any of the given (entity, score) pairs, ranked by score.
+    // Rejected facts are never returned; unreviewed ones only if requested.
+    // With `include_neighbours`, facts one hop away from a direct match are
+    // added at half the score of the entity that led to them.
+    pub async fn retrieve_structured_context(
+        &self,
+        entities: &[(String, f32)],
+        limit: usize,
+        include_neighbours: bool,
+        include_unreviewed: bool,
+    ) -> Result<Vec<KgFact>, String> {
+        let conn = self.conn.clone();
+        let entities = entities.to_vec();
+
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, subject, predicate, object FROM knowledge_graph
+                 WHERE (subject = ?1 COLLATE NOCASE OR object = ?1 COLLATE NOCASE)
+                   AND (status = 'confirmed' OR (?2 AND status = 'unreviewed'))"
+            ).map_err(|e| e.to_string())?;
+
+            let mut facts: HashMap<i64, KgFact> = HashMap::new();
+            let mut frontier: Vec<(String, f32)> = Vec::new();
+
+            let mut lookup = |node: &str, score: f32, hops: u8, facts: &mut HashMap<i64, KgFact>| -> Result<Vec<KgFact>, String> {
+                let rows = stmt.query_map(params![node, include_unreviewed], |row| {
+                    Ok(KgFact {
+                        id: row.get(0)?,
+                        subject: row.get(1)?,
+                        predicate: row.get(2)?,
+                        object: row.get(3)?,
+                        score,
+                        hops,
+                    })
+                }).map_err(|e| e.to_string())?;
+
+                let mut found = Vec::new();
+                for row in rows {
+                    let fact = row.map_err(|e| e.to_string())?;
+                    let better = facts.get(&fact.id).is_none_or(|f| f.score < fact.score);
+                    if better {
+                        facts.insert(fact.id, fact.clone());
+                        found.push(fact);
+                    }
+                }
+                Ok(found)
+            };
+
+            for (entity, score) in &entities {
+                for fact in lookup(entity, *score, 0, &mut facts)? {
+                    let other = if fact.subject.eq_ignore_ascii_case(entity) { fact.object } else { fact.subject };
+                    frontier.push((other, score * 0.5));
+                }
+            }
+
+            if include_neighbours {
+                for (node, score) in &frontier {
+                    lookup(node, *score, 1, &mut facts)?;
+                }
+            }
+
+            let mut results: Vec<KgFact> = facts.into_values().collect();
+            results.sort_by(|a, b| {
+                b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then(a.id.cmp(&b.id))
+            });
+            results.truncate(limit);
+            Ok::<Vec<KgFact>, String>(results)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // --- Layer 2: Semantic Memory (SQLite + ANN index) ---
+
+    // Identifies the vectors the configured embedder produces; stored
+    // vectors from any other embedder are not compared against them.
+    pub fn embedder_id(&self) -> EmbedderId {
+        self.embedder().id()
+    }
+
+    pub fn embedder(&self) -> Arc<dyn Embedder> {
+        self.embedder.read().unwrap().clone()
+    }
+
+    // Whether a reindex would bring the stored vectors up to date: some
+    // were embedded by an older version of the embedder, or its corpus
+    // statistics have outgrown their snapshot.
+    pub fn reindex_due(&self) -> bool {
+        self.index.lock().unwrap().outdated > 0 || self.embedder().stale()
+    }
+
+    // Text and vector are written in one row, so a memory is either fully
+    // stored or not at all.
+    pub async fn store_semantic_memory(&self, text: &str, metadata: &MemoryMetadata) -> Result<(), String> {
+        let ranges = chunker::chunk(text, &self.policy.chunking);
+        if ranges.len() > 1 {
+            return self.store_chunked_memory(text, ranges, metadata).await;
+        }
+
+        let conn = self.conn.clone();
+        let text_content = text.to_string();
+
+        // 1. Generate Embedding with the configured embedder
+        let embedder = self.embedder();
+        let embedding = embed_one(embedder.as_ref(), &text_content).await?;
+
+        // 2. A near-duplicate from the same source absorbs this one. Notes
+        // the user asked to remember are always kept as given.
+        let now = to_timestamp(chrono::Local::now());
+        let metadata = metadata.clone();
+        let blob = embedding::encode(&embedder.id(), &embedding);
+        if self.policy.dedup_threshold <= 1.0 && metadata.source != MemorySource::Chat {
+            let similar: Vec<String> = self
+                .nearest(&embedding, DEDUP_CANDIDATES, None)
+                .await?
+                .into_iter()
+                .filter(|(_, sim)| *sim >= self.policy.dedup_threshold)
+                .map(|(id, _)| id)
+                .collect();
+            if !similar.is_empty() {
+                let conn = conn.clone();
+                let metadata = metadata.clone();
+                let now = now.clone();
+                let text = text_content.clone();
+                let blob = blob.clone();
+                let merged = task::spawn_blocking(move || {
+                    let conn = conn.lock().unwrap();
+                    merge_duplicate(&conn, &similar, &metadata, &now, &text, &blob)
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+                if let Some(id) = merged {
+                    return self.update_semantic_index(move |index| index.insert(&id, embedding)).await;
+                }
+            }
+        }
+
+        // 3. Save text, vector and checksum together
+        let id = uuid::Uuid::new_v4().to_string();
+        let row_id = id.clone();
+        let importance = self.policy.importance.get(metadata.source.as_str()).copied().unwrap_or(0.5);
+        let tags = serde_json::to_string(&metadata.tags).map_err(|e| e.to_string())?;
+        let stored_text = text_content.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO semantic_memory (id, text, vector, created_at, checksum, source, tool, session_id, tags, updated_at, importance)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?4, ?10)",
+                params![
+                    row_id,
+                    text_content,
+                    blob,
+                    now,
+                    memory_checksum(&text_content, &blob),
+                    metadata.source.as_str(),
+                    metadata.tool,
+                    metadata.session_id,
+                    tags,
+                    importance
+                ],
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        // Merged duplicates never get here, so they don't count towards
+        // corpus statistics
+        embedder.observe(&stored_text);
+
+        // 4. Add to the in-memory index
+        self.update_semantic_index(move |index| index.insert(&id, embedding)).await
+    }
+
+    // Stores each chunk as its own memory under a shared parent id. A
+    // document whose every chunk near-duplicates a chunk of one existing
+    // document of the same source and length is merged into it instead,
+    // unless the user asked to remember it.
+    async fn store_chunked_memory(
+        &self,
+        text: &str,
+        ranges: Vec<std::ops::Range<usize>>,
+        metadata: &MemoryMetadata,
+    ) -> Result<(), String> {
+        let texts: Vec<String> = ranges.iter().map(|r| text[r.clone()].to_string()).collect();
+        let corpus = self.embedder();
+        let vectors = corpus.embed(&texts).await?;
+        if vectors.len() != texts.len() {
+            return Err(format!("Embedder returned {} vectors for {} chunks", vectors.len(), texts.len()));
+        }
+
+        let now = to_timestamp(chrono::Local::now());
+        let embedder = corpus.id();
+        let chunks: Vec<(String, Vec<u8>, std::ops::Range<usize>)> = texts
+            .iter()
+            .zip(&vectors)
+            .zip(&ranges)
+            .map(|((text, vector), range)| (text.clone(), embedding::encode(&embedder, vector), range.clone()))
+            .collect();
+        if self.policy.dedup_threshold <= 1.0 && metadata.source != MemorySource::Chat {
+            let mut similar: Vec<Vec<String>> = Vec::new();
+            for vector in &vectors {
+                similar.push(
+                    self.nearest(vector, DEDUP_CANDIDATES, None)
+                        .await?
+                        .into_iter()
+                        .filter(|(_, sim)| *sim >= self.policy.dedup_threshold)
+                        .map(|(id, _)| id)
+                        .collect(),
+                );
+            }
+            if similar.iter().all(|ids| !ids.is_empty()) {
+                let conn = self.conn.clone();
+                let metadata = metadata.clone();
+                let now = now.clone();
+                let chunks = chunks.clone();
+                let merged = task::spawn_blocking(move || {
+                    let conn = conn.lock().unwrap();
+                    merge_duplicate_document(&conn, &similar, &metadata, &now, &chunks)
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+                if let Some(ids) = merged {
+                    return self
+                        .update_semantic_index(move |index| {
+                            for (id, vector) in ids.iter().zip(vectors) {
+                                index.insert(id, vector);
+                            }
+                        })
+                        .await;
+                }
+            }
+        }
+
+        let parent_id = uuid::Uuid::new_v4().to_string();
+        let ids: Vec<String> = texts.iter().map(|_| uuid::Uuid::new_v4().to_string()).collect();
+        let importance = self.policy.importance.get(metadata.source.as_str()).copied().unwrap_or(0.5);
+        let tags = serde_json::to_string(&metadata.tags).map_err(|e| e.to_string())?;
+        let metadata = metadata.clone();
+        let conn = self.conn.clone();
+        let rows: Vec<(String, String, Vec<u8>, std::ops::Range<usize>)> = ids
+            .iter()
+            .zip(chunks)
+            .map(|(id, (text, blob, range))| (id.clone(), text, blob, range))
+            .collect();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            for (index, (id, text, blob, range)) in rows.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO semantic_memory (id, text, vector, created_at, checksum, source, tool, session_id, tags, updated_at,
+                                                  importance, parent_id, chunk_index, chunk_start, chunk_end)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?4, ?10, ?11, ?12, ?13, ?14)",
+                    params![
+                        id,
+                        text,
+                        blob,
+                        now,
+                        memory_checksum(text, blob),
+                        metadata.source.as_str(),
+                        metadata.tool,
+                        metadata.session_id,
+                        tags,
+                        importance,
+                        parent_id,
+                        index as i64,
+                        range.start as i64,
+                        range.end as i64
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+            for (_, text, _, _) in &rows {
+                corpus.observe(text);
+            }
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        self.update_semantic_index(move |index| {
+            for (id, vector) in ids.iter().zip(vectors) {
+                index.insert(id, vector);
+            }
+        })
+        .await
+    }
+
+    // The text of a chunked document, rebuilt from the chunks that remain.
+    async fn reassemble_parent(&self, parent_id: &str) -> Result<String, String> {
+        let conn = self.conn.clone();
+        let parent_id = parent_id.to_string();
+        let chunks = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT chunk_start, text FROM semantic_memory WHERE parent_id = ?1 ORDER BY chunk_start")
+                .map_err(|e| e.to_string())?;
+            let chunks = stmt
+                .query_map(params![parent_id], |row| Ok((row.get::<_, i64>(0)? as usize, row.get::<_, String>(1)?)))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok::<Vec<(usize, String)>, String>(chunks)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Ok(chunker::reassemble(&chunks))
+    }
+
+    // Memories containing every word of `terms` as a whole word (through
+    // the full-text index), oldest first. Chunk hits resolve to their
+    // document: returns (document id, text), where a chunked document's id
+    // is its parent_id and its text the reassembled document.
+    pub async fn find_semantic_memories(&self, terms: &str) -> Result<Vec<(String, String)>, String> {
+        let Some(fts) = fts_query(&query_words(terms), "AND") else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.clone();
+        let rows = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT m.id, m.text, m.parent_id FROM semantic_memory_fts f
+                     JOIN semantic_memory m ON m.id = f.id
+                     WHERE semantic_memory_fts MATCH ?1
+                     ORDER BY m.created_at",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![fts], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+                })
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok::<Vec<(String, String, Option<String>)>, String>(rows)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let mut seen = HashSet::new();
+        let mut documents = Vec::new();
+        for (id, text, parent_id) in rows {
+            match parent_id {
+                Some(parent_id) => {
+                    if seen.insert(parent_id.clone()) {
+                        let text = self.reassemble_parent(&parent_id).await?;
+                        documents.push((parent_id, text));
+                    }
+                }
+                None => {
+                    if seen.insert(id.clone()) {
+                        documents.push((id, text));
+                    }
+                }
+            }
+        }
+        Ok(documents)
+    }
+
+    // Deletes a memory, or every chunk of a chunked document given its
+    // parent id. Returns how many rows went.
+    pub async fn delete_semantic_document(&self, id: &str) -> Result<usize, String> {
+        self.delete_memories_where("id = ?1 OR parent_id = ?1".to_string(), vec![Value::Text(id.to_string())])
+            .await
+    }
+
+    // Returns false if there was no such memory.
+    pub async fn delete_semantic_memory(&self, id: &str) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        let row_id = id.to_string();
+        let deleted = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute("DELETE FROM semantic_memory WHERE id = ?1", params![row_id])
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let id = id.to_string();
+        self.update_semantic_index(move |index| {
+            index.remove(&id);
+        })
+        .await?;
+        Ok(deleted > 0)
+    }
+
+    // Deletes every memory matching the filter and returns how many. The
+    // filter must restrict something; min_score is ignored.
+    pub async fn delete_semantic_memories(&self, filter: &MemoryFilter) -> Result<usize, String> {
+        if !filter.restricts() {
+            return Err("Refusing to delete without a filter".to_string());
+        }
+        let (condition, values) = filter.to_sql()?;
+        self.delete_memories_where(condition, values).await
+    }
+
+    // Deletes memories not seen (stored or merged into) within their
+    // source's TTL. Returns the number removed.
+    pub async fn expire_semantic_memories(&self, ttl_days: &HashMap<String, u64>) -> Result<usize, String> {
+        let mut removed = 0;
+        for (source, days) in ttl_days {
+            let cutoff = to_timestamp(chrono::Local::now() - chrono::Duration::days(*days as i64));
+            let condition = "source = ? AND updated_at < ?".to_string();
+            let values = vec![Value::Text(source.clone()), Value::Text(cutoff)];
+            removed += self.delete_memories_where(condition, values).await?;
+        }
+        Ok(removed)
+    }
+
+    async fn delete_memories_where(&self, condition: String, values: Vec<Value>) -> Result<usize, String> {
+        let conn = self.conn.clone();
+        let ids = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            let ids = {
+                let mut stmt = tx
+                    .prepare(&format!("SELECT id FROM semantic_memory WHERE {}", condition))
+                    .map_err(|e| e.to_string())?;
+                let ids = stmt
+                    .query_map(params_from_iter(values), |row| row.get::<_, String>(0))
+                    .map_err(|e| e.to_string())?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())?;
+                ids
+            };
+            for id in &ids {
+                tx.execute("DELETE FROM semantic_memory WHERE id = ?1", params![id])
+                    .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok::<Vec<String>, String>(ids)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let count = ids.len();
+        if count > 0 {
+            self.update_semantic_index(move |index| {
+                for id in &ids {
+                    index.remove(id);
+                }
+            })
+            .await?;
+        }
+        Ok(count)
+    }
+
+    // Up to k memories for a query, ranked by `config.mode` and limited to
+    // those matching `filter`. Hybrid retrieval fuses the vector and BM25
+    // rankings; each hit carries the breakdown. Hits scoring below the
+    // minimum score are dropped, so fewer than k may come back.
+    pub async fn retrieve_semantic_context(
+        &self,
+        query: &str,
+        k: usize,
+        config: &RetrievalConfig,
+        filter: &MemoryFilter,
+    ) -> Result<Vec<SemanticHit>, String> {
+        let (use_vector, use_lexical) = match config.mode.as_str() {
+            "vector" => (true, false),
+            "lexical" => (false, true),
+            "hybrid" => (true, true),
+            other => return Err(format!("Unknown retrieval mode '{}' (expected 'vector', 'lexical' or 'hybrid')", other)),
+        };
+        let pool = config.candidates.max(k);
+        let min_score = filter.min_score.unwrap_or(config.min_score);
+        let (condition, values) = filter.to_sql()?;
+
+        // 0. Memories the filter allows, if it restricts anything
+        let allowed: Option<HashSet<String>> = if filter.restricts() {
+            let conn = self.conn.clone();
+            let sql = format!("SELECT id FROM semantic_memory WHERE {}", condition);
+            let values = values.clone();
+            Some(
+                task::spawn_blocking(move || {
+                    let conn = conn.lock().unwrap();
+                    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+                    let ids = stmt
+                        .query_map(params_from_iter(values), |row| row.get::<_, String>(0))
+                        .map_err(|e| e.to_string())?
+                        .collect::<Result<HashSet<_>, _>>()
+                        .map_err(|e| e.to_string())?;
+                    Ok::<HashSet<String>, String>(ids)
+                })
+                .await
+                .map_err(|e| e.to_string())??,
+            )
+        } else {
+            None
+        };
+        if allowed.as_ref().is_some_and(|ids| ids.is_empty()) {
+            return Ok(Vec::new());
+        }
+
+        // 1. Approximate nearest neighbours from the in-memory index
+        let vector_hits = if use_vector {
+            let query_vec = embed_one(self.embedder().as_ref(), query).await?;
+            let mut hits = self.nearest(&query_vec, pool, allowed.as_ref()).await?;
+            hits.retain(|(_, similarity)| *similarity >= min_score);
+            hits
+        } else {
+            Vec::new()
+        };
+
+        // 2. BM25 ranking from the full-text index, matching any of the
+        // query's words other than stop words
+        let terms = content_words(query);
+        let lexical_hits = match fts_query(&terms, "OR") {
+            Some(fts) if use_lexical => {
+                let conn = self.conn.clone();
+                let sql = format!(
+                    "SELECT id, bm25(semantic_memory_fts), text FROM semantic_memory_fts
+                     WHERE semantic_memory_fts MATCH ?
+                       AND id IN (SELECT id FROM semantic_memory WHERE {})
+                     ORDER BY bm25(semantic_memory_fts) LIMIT ?",
+                    condition
+                );
+                let mut values = values.clone();
+                values.insert(0, Value::Text(fts));
+                values.push(Value::Integer(pool as i64));
+                task::spawn_blocking(move || {
+                    let conn = conn.lock().unwrap();
+                    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+                    let rows = stmt
+                        // bm25() is lower-is-better; flip it so higher is better
+                        .query_map(params_from_iter(values), |row| {
+                            let text: String = row.get(2)?;
+                            Ok((row.get::<_, String>(0)?, -row.get::<_, f64>(1)? as f32, term_overlap(&terms, &text)))
+                        })
+                        .map_err(|e| e.to_string())?
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| e.to_string())?;
+                    Ok::<Vec<(String, f32, f32)>, String>(rows)
+                })
+                .await
+                .map_err(|e| e.to_string())??
+                .into_iter()
+                .filter(|(_, bm25, _)| *bm25 >= min_score)
+                .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // 3. Fuse, then retrieve text and metadata
+        let ranked = if use_lexical {
+            // Only vectors with some similarity take part
+            let vector_hits: Vec<_> = vector_hits.into_iter().filter(|(_, sim)| *sim > 0.0).collect();
+            fuse_rankings(&vector_hits, &lexical_hits, use_vector, config)
+        } else {
+            // Vector-only keeps cosine similarity as the score
+            vector_hits
+                .iter()
+                .enumerate()
+                .map(|(rank, (id, similarity))| {
+                    let breakdown = ScoreBreakdown {
+                        vector_rank: Some(rank + 1),
+                        similarity: Some(*similarity),
+                        ..ScoreBreakdown::default()
+                    };
+                    (id.clone(), *similarity, breakdown)
+                })
+                .collect()
+        };
+
+        let conn = self.conn.clone();
+        let mut hits = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(&format!("SELECT {} FROM semantic_memory WHERE id = ?1", MEMORY_COLUMNS))
+                .map_err(|e| e.to_string())?;
+            let mut results = Vec::new();
+            for (id, score, breakdown) in ranked {
+                if let Ok(memory) = stmt.query_row(params![id], read_memory_row) {
+                    results.push(SemanticHit { memory, score, breakdown });
+                }
+            }
+            Ok::<Vec<SemanticHit>, String>(results)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        // 4. Weight by recency and importance
+        let now = chrono::Local::now();
+        for hit in &mut hits {
+            weigh_hit(hit, now, config);
+        }
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.memory.id.cmp(&b.memory.id)));
+
+        // 5. Optionally swap chunks for their whole document, keeping each
+        // document once at its best chunk's rank
+        if config.return_parents {
+            let mut seen = HashSet::new();
+            hits.retain(|hit| match &hit.memory.chunk {
+                Some(chunk) => seen.insert(chunk.parent_id.clone()),
+                None => true,
+            });
+            hits.truncate(k);
+            for hit in &mut hits {
+                if let Some(chunk) = &hit.memory.chunk {
+                    hit.memory.text = self.reassemble_parent(&chunk.parent_id).await?;
+                }
+            }
+        }
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    // Feeds every stored text to the embedder's corpus statistics.
+    async fn prime_embedder(&self) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let embedder = self.embedder();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT text FROM semantic_memory").map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+            for text in rows {
+                embedder.observe(&text.map_err(|e| e.to_string())?);
+            }
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Freezes the embedder's corpus statistics at the snapshot the last
+    // reindex saved or, the first time, at everything primed so far.
+    async fn restore_embedder_state(&self) -> Result<(), String> {
+        let current = self.embedder();
+        let Some(primed) = current.corpus_state() else {
+            return Ok(());
+        };
+        let name = current.id().name;
+        let conn = self.conn.clone();
+        let key = name.clone();
+        let saved: Option<String> = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row("SELECT state FROM embedder_state WHERE embedder = ?1", params![key], |row| row.get(0))
+                .ok()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let restored = match saved.map(|state| current.with_state(&state)) {
+            Some(Ok(embedder)) => embedder,
+            unusable => {
+                if let Some(Err(e)) = unusable {
+                    eprintln!("Saved {} statistics are unusable ({}), freezing the current ones", name, e);
+                }
+                let embedder = current.with_state(&primed)?;
+                let conn = self.conn.clone();
+                task::spawn_blocking(move || {
+                    let conn = conn.lock().unwrap();
+                    conn.execute(
+                        "INSERT OR REPLACE INTO embedder_state (embedder, state) VALUES (?1, ?2)",
+                        params![name, primed],
+                    )
+                    .map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+                embedder
+            }
+        };
+        *self.embedder.write().unwrap() = restored;
+        Ok(())
+    }
+
+    // One-time import of the old flat-file layout (text/<id>.txt plus
+    // vectors/<id>.bin) in a single transaction. The old vectors were hashed
+    // unstably, so every text is re-embedded. Ids already in the database
+    // are skipped and unreadable files are logged and left out. The files
+    // are then merged into migrated/ so the import never runs twice, even
+    // if the old directories reappear.
+    async fn migrate_flat_file_memories(&self) -> Result<(), String> {
+        if !Path::new(LEGACY_TEXT_DIR).exists() && !Path::new(LEGACY_VECTOR_DIR).exists() {
+            return Ok(());
+        }
+        let text_ids = legacy_ids(LEGACY_TEXT_DIR, "txt")?;
+        let orphans = legacy_ids(LEGACY_VECTOR_DIR, "bin")?.difference(&text_ids).count();
+
+        let conn = self.conn.clone();
+        let existing: HashSet<String> = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT id FROM semantic_memory").map_err(|e| e.to_string())?;
+            let ids = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<HashSet<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok::<HashSet<String>, String>(ids)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        let mut rows = Vec::new();
+        let (mut already, mut unreadable) = (0, 0);
+        for id in text_ids {
+            if existing.contains(&id) {
+                already += 1;
+                continue;
+            }
+            let text_path = format!("{}/{}.txt", LEGACY_TEXT_DIR, id);
+            let text = match fs::read_to_string(&text_path) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("Skipping legacy memory {}: {}", text_path, e);
+                    unreadable += 1;
+                    continue;
+                }
+            };
+            let created_at = fs::metadata(&text_path)
+                .and_then(|m| m.modified())
+                .map(|t| to_timestamp(t.into()))
+                .unwrap_or_else(|_| to_timestamp(chrono::Local::now()));
+            rows.push((id, text, created_at));
+        }
+        let texts: Vec<String> = rows.iter().map(|(_, text, _)| text.clone()).collect();
+        let corpus = self.embedder();
+        for text in &texts {
+            corpus.observe(text);
+        }
+        let vectors = if texts.is_empty() { Vec::new() } else { corpus.embed(&texts).await? };
+        let embedder = corpus.id();
+
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap();
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            let mut imported = 0;
+            for ((id, text, created_at), vector) in rows.iter().zip(vectors) {
+                let blob = embedding::encode(&embedder, &vector);
+                imported += tx
+                    .execute(
+                        "INSERT OR IGNORE INTO semantic_memory (id, text, vector, created_at, checksum, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?4)",
+                        params![id, text, blob, created_at, memory_checksum(text, &blob)],
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+            backfill_trace_tools(&tx)?;
+            tx.commit().map_err(|e| e.to_string())?;
+
+            for (dir, name) in [(LEGACY_TEXT_DIR, "text"), (LEGACY_VECTOR_DIR, "vectors")] {
+                merge_legacy_dir(dir, &format!("{}/{}", MIGRATED_DIR, name))?;
+            }
+            println!(
+                "Migrated {} semantic memories from flat files ({} already imported, {} unreadable, {} orphaned vectors skipped); originals moved to {}",
+                imported, already, unreadable, orphans, MIGRATED_DIR
+            );
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Loads the index file, or rebuilds it from the stored vectors if it is
+    // missing, unreadable, built by another embedder or `rebuild` is set,
+    // then reconciles it with the database. Rows whose checksum doesn't
+    // match, or whose vector came from a different embedder, are left out
+    // of the index.
+    async fn load_semantic_index(&self, rebuild: bool) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let state = self.index.clone();
+        let embedder = self.embedder_id();
+        let quantization = self.quantization;
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            upgrade_unversioned_vectors(&conn)?;
+
+            let label = embedder.to_string();
+            let stored = stored_memory_ids(&conn)?;
+            let loaded = if rebuild {
+                Err("rebuild requested".to_string())
+            } else {
+                VectorIndex::load(INDEX_PATH, &label, embedder.dim, quantization)
+            };
+            let (mut index, rebuilt) = match loaded {
+                Ok(index) => (index, false),
+                Err(e) => {
+                    if !rebuild && Path::new(INDEX_PATH).exists() {
+                        eprintln!("Semantic index at {} is unusable ({}), rebuilding", INDEX_PATH, e);
+                    }
+                    (VectorIndex::new(&label, embedder.dim, quantization), true)
+                }
+            };
+
+            // Memories written after the last save, or deleted since.
+            let indexed = index.ids();
+            let (mut changes, mut incompatible, mut outdated) = (0, 0, 0);
+            for id in stored.difference(&indexed) {
+                match read_stored_vector(&conn, id)? {
+                    Some(stored) if stored.embedder == embedder => {
+                        index.insert(id, stored.values);
+                        changes += 1;
+                    }
+                    Some(stored) => {
+                        incompatible += 1;
+                        if stored.embedder.name == embedder.name {
+                            outdated += 1;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            for id in indexed.difference(&stored) {
+                index.remove(id);
+                changes += 1;
+            }
+            if incompatible > 0 {
+                eprintln!(
+                    "{} semantic memories were embedded by a different embedder than {} and are not searchable until reindexed",
+                    incompatible, label
+                );
+            }
+
+            if rebuilt || changes > 0 {
+                if index.deleted_ratio() > INDEX_MAX_DELETED {
+                    index = index.compacted();
+                }
+                index.save(INDEX_PATH)?;
+            }
+            println!(
+                "Semantic index: {} memories, {}, {} vectors ({})",
+                index.len(),
+                label,
+                quantization.as_str(),
+                if rebuilt { "rebuilt".to_string() } else { format!("loaded, {} reconciled", changes) }
+            );
+            *state.lock().unwrap() = IndexState { index, unsaved: 0, outdated };
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // --- Reindex generations ---
+
+    // Resumes the interrupted job for this embedder, or starts a new
+    // generation, and returns the embedder to re-embed with. One that keeps
+    // corpus statistics re-embeds under a new snapshot of them, recorded
+    // with the job so a resumed run uses the same one. Running jobs for
+    // other embedders are abandoned.
+    pub async fn start_or_resume_reindex(&self) -> Result<(ReindexJob, Arc<dyn Embedder>), String> {
+        let current = self.embedder();
+        let conn = self.conn.clone();
+        let running: Option<(i64, String, Option<String>)> = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT generation, embedder, embedder_state FROM reindex_jobs
+                 WHERE status = 'running' ORDER BY generation DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .ok()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Some((generation, label, state)) = running {
+            let resumed = match &state {
+                Some(state) => current.with_state(state).ok(),
+                None => Some(current.clone()),
+            };
+            if let Some(embedder) = resumed.filter(|e| e.id().to_string() == label) {
+                let conn = self.conn.clone();
+                let job = task::spawn_blocking(move || read_reindex_job(&conn.lock().unwrap(), generation))
+                    .await
+                    .map_err(|e| e.to_string())??;
+                return Ok((job, embedder));
+            }
+        }
+
+        let state = current.corpus_state();
+        let embedder = match &state {
+            Some(state) => current.with_state(state)?,
+            None => current,
+        };
+        let label = embedder.id().to_string();
+        let conn = self.conn.clone();
+        let job = task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            tx.execute(
+                "DELETE FROM reindex_staging WHERE generation IN (SELECT generation FROM reindex_jobs WHERE status = 'running')",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE reindex_jobs SET status = 'abandoned', finished_at = ?1 WHERE status = 'running'",
+                params![to_timestamp(chrono::Local::now())],
+            )
+            .map_err(|e| e.to_string())?;
+            let total: i64 = tx
+                .query_row("SELECT COUNT(*) FROM semantic_memory", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO reindex_jobs (embedder, embedder_state, status, total, started_at) VALUES (?1, ?2, 'running', ?3, ?4)",
+                params![label, state, total, to_timestamp(chrono::Local::now())],
+            )
+            .map_err(|e| e.to_string())?;
+            let generation = tx.last_insert_rowid();
+            tx.commit().map_err(|e| e.to_string())?;
+            read_reindex_job(&conn, generation)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Ok((job, embedder))
+    }
+
+    // Next memories without a staged vector in this generation, as (id, text).
+    pub async fn reindex_pending(&self, generation: i64, limit: usize) -> Result<Vec<(String, String)>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, text FROM semantic_memory
+                     WHERE id NOT IN (SELECT id FROM reindex_staging WHERE generation = ?1)
+                     ORDER BY id LIMIT ?2",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![generation, limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok::<Vec<(String, String)>, String>(rows)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Stages one batch in a single transaction, so an interrupted run
+    // resumes after the last complete batch.
+    pub async fn stage_reindex_batch(
+        &self,
+        generation: i64,
+        embedder: &EmbedderId,
+        rows: Vec<(String, String)>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Result<ReindexJob, String> {
+        let conn = self.conn.clone();
+        let embedder = embedder.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            for ((id, text), vector) in rows.iter().zip(vectors) {
+                let blob = embedding::encode(&embedder, &vector);
+                tx.execute(
+                    "INSERT OR REPLACE INTO reindex_staging (generation, id, vector, checksum) VALUES (?1, ?2, ?3, ?4)",
+                    params![generation, id, blob, memory_checksum(text, &blob)],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+            read_reindex_job(&conn, generation)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn fail_reindex(&self, generation: i64, error: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let error = error.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute("UPDATE reindex_jobs SET error = ?1 WHERE generation = ?2", params![error, generation])
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Ok(())
+    }
+
+    // Takes the reindex lock, returning the owner token to refresh and
+    // release it with.
+    pub async fn acquire_reindex_lock(&self) -> Result<String, String> {
+        let conn = self.conn.clone();
+        let owner = uuid::Uuid::new_v4().to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let now = chrono::Utc::now().timestamp();
+            conn.execute(
+                "DELETE FROM reindex_lock WHERE heartbeat_at < ?1",
+                params![now - REINDEX_LOCK_STALE_SECONDS],
+            )
+            .map_err(|e| e.to_string())?;
+            let taken = conn
+                .execute(
+                    "INSERT OR IGNORE INTO reindex_lock (id, owner, heartbeat_at) VALUES (1, ?1, ?2)",
+                    params![owner, now],
+                )
+                .map_err(|e| e.to_string())?;
+            if taken == 0 {
+                return Err("A reindex is already running".to_string());
+            }
+            Ok(owner)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn refresh_reindex_lock(&self, owner: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let owner = owner.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let held = conn
+                .execute(
+                    "UPDATE reindex_lock SET heartbeat_at = ?1 WHERE owner = ?2",
+                    params![chrono::Utc::now().timestamp(), owner],
+                )
+                .map_err(|e| e.to_string())?;
+            if held == 0 {
+                return Err("Lost the reindex lock to another process".to_string());
+            }
+            Ok(())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    pub async fn release_reindex_lock(&self, owner: &str) -> Result<(), String> {
+        let conn = self.conn.clone();
+        let owner = owner.to_string();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute("DELETE FROM reindex_lock WHERE owner = ?1", params![owner])
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Whether some process holds a live reindex lock.
+    pub async fn reindex_locked(&self) -> Result<bool, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT COUNT(*) FROM reindex_lock WHERE heartbeat_at >= ?1",
+                params![chrono::Utc::now().timestamp() - REINDEX_LOCK_STALE_SECONDS],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n > 0)
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Swaps the staged vectors in atomically, along with the snapshot they
+    // were embedded under, then serves `embedder` and rebuilds the index.
+    pub async fn finish_reindex(&self, generation: i64, embedder: Arc<dyn Embedder>) -> Result<ReindexJob, String> {
+        let conn = self.conn.clone();
+        let name = embedder.id().name;
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE semantic_memory SET
+                    vector = (SELECT s.vector FROM reindex_staging s WHERE s.generation = ?1 AND s.id = semantic_memory.id),
+                    checksum = (SELECT s.checksum FROM reindex_staging s WHERE s.generation = ?1 AND s.id = semantic_memory.id)
+                 WHERE id IN (SELECT id FROM reindex_staging WHERE generation = ?1)",
+                params![generation],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE reindex_jobs SET total = (SELECT COUNT(*) FROM reindex_staging WHERE generation = ?1) WHERE generation = ?1",
+                params![generation],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute("DELETE FROM reindex_staging WHERE generation = ?1", params![generation])
+                .map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT OR REPLACE INTO embedder_state (embedder, state)
+                 SELECT ?2, embedder_state FROM reindex_jobs WHERE generation = ?1 AND embedder_state IS NOT NULL",
+                params![generation, name],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.execute(
+                "UPDATE reindex_jobs SET status = 'complete', finished_at = ?1, error = NULL WHERE generation = ?2",
+                params![to_timestamp(chrono::Local::now()), generation],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        *self.embedder.write().unwrap() = embedder;
+        self.load_semantic_index(true).await?;
+        self.latest_reindex_job().await?.ok_or_else(|| "Reindex job disappeared".to_string())
+    }
+
+    pub async fn latest_reindex_job(&self) -> Result<Option<ReindexJob>, String> {
+        let conn = self.conn.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let generation: Option<i64> = conn
+                .query_row("SELECT MAX(generation) FROM reindex_jobs", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            generation.map(|g| read_reindex_job(&conn, g)).transpose()
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Every memory's full-precision vector from the current embedder, in
+    // id order; rows that fail their checksum are left out as in the index.
+    pub async fn indexed_vectors(&self) -> Result<Vec<(String, Vec<f32>)>, String> {
+        let conn = self.conn.clone();
+        let embedder = self.embedder_id();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut ids: Vec<String> = stored_memory_ids(&conn)?.into_iter().collect();
+            ids.sort();
+            let mut vectors = Vec::new();
+            for id in ids {
+                if let Some(stored) = read_stored_vector(&conn, &id)? {
+                    if stored.embedder == embedder {
+                        vectors.push((id, stored.values));
+                    }
+                }
+            }
+            Ok::<Vec<(String, Vec<f32>)>, String>(vectors)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Nearest indexed memories by cosine similarity, optionally only among
+    // `allow`. A quantized index only shortlists: `oversample` times as many
+    // candidates are re-scored against their full-precision vectors.
+    async fn nearest(&self, query: &[f32], k: usize, allow: Option<&HashSet<String>>) -> Result<Vec<(String, f32)>, String> {
+        let quantized = self.quantization != Quantization::None;
+        let pool = if quantized { k * self.policy.quantization.oversample.max(1) } else { k };
+        let candidates = {
+            let state = self.index.lock().unwrap();
+            match allow {
+                Some(ids) => state.index.search_filtered(query, pool, |id| ids.contains(id)),
+                None => state.index.search(query, pool),
+            }
+        };
+        if !quantized || candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        let conn = self.conn.clone();
+        let query = query.to_vec();
+        let embedder = self.embedder_id();
+        task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT vector FROM semantic_memory WHERE id = ?1")
+                .map_err(|e| e.to_string())?;
+            let mut rescored = Vec::new();
+            for (id, _) in candidates {
+                let blob = match stmt.query_row(params![id], |row| row.get::<_, Vec<u8>>(0)) {
+                    Ok(blob) => blob,
+                    // Deleted since the search
+                    Err(rusqlite::Error::QueryReturnedNoRows) => continue,
+                    Err(e) => return Err(e.to_string()),
+                };
+                // Rows re-embedded by a reindex since the index was built are
+                // not comparable with the query
+                match embedding::decode(&blob) {
+                    Ok(stored) if stored.embedder == embedder => {
+                        let similarity = cosine_similarity(&query, &stored.values);
+                        rescored.push((id, similarity));
+                    }
+                    _ => {}
+                }
+            }
+            rescored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            rescored.truncate(k);
+            Ok::<Vec<(String, f32)>, String>(rescored)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // Applies a change to the index and persists it every few changes.
+    async fn update_semantic_index<F>(&self, change: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut VectorIndex) + Send + 'static,
+    {
+        let state = self.index.clone();
+        task::spawn_blocking(move || {
+            let mut state = state.lock().unwrap();
+            change(&mut state.index);
+            state.unsaved += 1;
+            if state.unsaved < INDEX_SAVE_EVERY {
+                return Ok(());
+            }
+            if state.index.deleted_ratio() > INDEX_MAX_DELETED {
+                state.index = state.index.compacted();
+            }
+            state.unsaved = 0;
+            state.index.save(INDEX_PATH)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+// Folds a new memory into the most similar existing one of the same
+// source: the newer text and vector replace the stored ones, the hit count
+// and last-seen time are bumped and any new tags added. Returns the merged
+// id, or None if none of the candidates share the source.
+fn merge_duplicate(
+    conn: &Connection,
+    candidates: &[String],
+    metadata: &MemoryMetadata,
+    now: &str,
+    text: &str,
+    blob: &[u8],
+) -> Result<Option<String>, String> {
+    for id in candidates {
+        let tags: Option<String> = conn
+            .query_row(
+                "SELECT tags FROM semantic_memory WHERE id = ?1 AND source = ?2 AND parent_id IS NULL",
+                params![id, metadata.source.as_str()],
+                |row| row.get(0),
+            )
+            .ok();
+        let Some(tags) = tags else {
+            continue;
+        };
+        let mut tags: Vec<String> = serde_json::from_str(&tags).unwrap_or_default();
+        for tag in &metadata.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let tags = serde_json::to_string(&tags).map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE semantic_memory SET hit_count = hit_count + 1, updated_at = ?1, tags = ?2, text = ?3, vector = ?4, checksum = ?5
+             WHERE id = ?6",
+            params![now, tags, text, blob, memory_checksum(text, blob), id],
+        )
+        .map_err(|e| e.to_string())?;
+        return Ok(Some(id.clone()));
+    }
+    Ok(None)
+}
+
+// Chunked counterpart of merge_duplicate: `similar[i]` holds the ids near
+// the new document's chunk i. An existing document of the same source and
+// chunk count that every new chunk is near absorbs the new one: each of
+// its chunks takes the text, vector and range of the new chunk at the same
+// position and is marked seen. Returns the merged chunk ids in order.
+fn merge_duplicate_document(
+    conn: &Connection,
+    similar: &[Vec<String>],
+    metadata: &MemoryMetadata,
+    now: &str,
+    chunks: &[(String, Vec<u8>, std::ops::Range<usize>)],
+) -> Result<Option<Vec<String>>, String> {
+    let mut common: Option<HashSet<String>> = None;
+    for ids in similar {
+        let mut parents = HashSet::new();
+        for id in ids {
+            let parent: Option<String> = conn
+                .query_row(
+                    "SELECT parent_id FROM semantic_memory WHERE id = ?1 AND source = ?2 AND parent_id IS NOT NULL",
+                    params![id, metadata.source.as_str()],
+                    |row| row.get(0),
+                )
+                .ok();
+            parents.extend(parent);
+        }
+        let narrowed: HashSet<String> = match common {
+            Some(common) => common.intersection(&parents).cloned().collect(),
+            None => parents,
+        };
+        if narrowed.is_empty() {
+            return Ok(None);
+        }
+        common = Some(narrowed);
+    }
+
+    let mut candidates: Vec<String> = common.unwrap_or_default().into_iter().collect();
+    candidates.sort();
+    for parent_id in candidates {
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM semantic_memory WHERE parent_id = ?1", params![parent_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if count as usize != similar.len() {
+            continue;
+        }
+        let tags: String = conn
+            .query_row("SELECT tags FROM semantic_memory WHERE parent_id = ?1 LIMIT 1", params![parent_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let mut tags: Vec<String> = serde_json::from_str(&tags).unwrap_or_default();
+        for tag in &metadata.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let tags = serde_json::to_string(&tags).map_err(|e| e.to_string())?;
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        let mut ids = Vec::with_capacity(chunks.len());
+        for (index, (text, blob, range)) in chunks.iter().enumerate() {
+            let id: String = tx
+                .query_row(
+                    "UPDATE semantic_memory SET hit_count = hit_count + 1, updated_at = ?1, tags = ?2, text = ?3, vector = ?4,
+                                                checksum = ?5, chunk_start = ?6, chunk_end = ?7
+                     WHERE parent_id = ?8 AND chunk_index = ?9 RETURNING id",
+                    params![
+                        now,
+                        tags,
+                        text,
+                        blob,
+                        memory_checksum(text, blob),
+                        range.start as i64,
+                        range.end as i64,
+                        parent_id,
+                        index as i64
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(|e| e.to_string())?;
+            ids.push(id);
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        return Ok(Some(ids));
+    }
+    Ok(None)
+}
+
+// Stable across builds: FNV-1a over the text and vector bytes.
+fn memory_checksum(text: &str, vector: &[u8]) -> String {
+    let bytes: Vec<u8> = text.as_bytes().iter().chain(vector).copied().collect();
+    format!("{:016x}", embedding::fnv1a64(&bytes))
+}
+
+// Rows stored before vectors carried a header were hashed with std's
+// unstable DefaultHasher; re-embed them with the stable hash embedder. If
+// another embedder is configured they then await a reindex like any other
+// incompatible vector.
+fn upgrade_unversioned_vectors(conn: &Connection) -> Result<(), String> {
+    let rows: Vec<(String, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, text FROM semantic_memory WHERE substr(vector, 1, 4) <> X'47414956'")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let embedder = embedding::simple_hash_id();
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for (id, text) in &rows {
+        let blob = embedding::encode(&embedder, &embedding::simple_hash_embedding(text));
+        tx.execute(
+            "UPDATE semantic_memory SET vector = ?1, checksum = ?2 WHERE id = ?3",
+            params![blob, memory_checksum(text, &blob), id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    println!("Re-embedded {} semantic memories with {}", rows.len(), embedder);
+    Ok(())
+}
+
+// Scales a hit's relevance by how recently its memory was seen and how
+// important it is. Repeats (hit_count) add to the stored importance.
+fn weigh_hit(hit: &mut SemanticHit, now: chrono::DateTime<chrono::Local>, config: &RetrievalConfig) {
+    let age_days = from_timestamp(&hit.memory.updated_at)
+        .map(|seen| (now - seen).num_seconds().max(0) as f32 / 86_400.0)
+        .unwrap_or(0.0);
+    let recency = if config.recency_half_life_days > 0.0 {
+        0.5f32.powf(age_days / config.recency_half_life_days)
+    } else {
+        1.0
+    };
+    let importance = (hit.memory.importance + 0.1 * (hit.memory.hit_count.max(1) as f32).ln()).min(1.0);
+
+    let base = (1.0 - config.recency_weight - config.importance_weight).max(0.0);
+    hit.breakdown.relevance = hit.score;
+    hit.breakdown.recency = recency;
+    hit.breakdown.importance = importance;
+    hit.score *= base + config.recency_weight * recency + config.importance_weight * importance;
+}
+
+// Lowercased words of the text, each once, in order.
+fn query_words(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .filter(|w| seen.insert(w.clone()))
+        .collect()
+}
+
+// Query words that say something about the content: stop words match
+// nearly every memory.
+fn content_words(text: &str) -> Vec<String> {
+    query_words(text).into_iter().filter(|w| !is_stopword(w)).collect()
+}
+
+// Share of `terms` found among the words of `text`.
+fn term_overlap(terms: &[String], text: &str) -> f32 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let words: HashSet<String> = query_words(text).into_iter().collect();
+    terms.iter().filter(|t| words.contains(*t)).count() as f32 / terms.len() as f32
+}
+
+// FTS5 query joining `words` with `operator` ("OR" matches any word, "AND"
+// all of them). Words are quoted so the user's text is never parsed as
+// query syntax.
+fn fts_query(words: &[String], operator: &str) -> Option<String> {
+    if words.is_empty() {
+        None
+    } else {
+        let quoted: Vec<String> = words.iter().map(|w| format!("\"{}\"", w)).collect();
+        Some(quoted.join(&format!(" {} ", operator)))
+    }
+}
+
+// Weighted reciprocal rank fusion of the vector and lexical rankings, best
+// first. Each ranking's share is scaled by how relevant the hit is to it
+// (cosine similarity, or the share of query words the memory contains), so
+// topping a ranking on a weak match doesn't make a strong score. Scores are
+// scaled so that a perfect match ranking first in every enabled list
+// scores 1.0.
+fn fuse_rankings(
+    vector: &[(String, f32)],
+    lexical: &[(String, f32, f32)],
+    use_vector: bool,
+    config: &RetrievalConfig,
+) -> Vec<(String, f32, ScoreBreakdown)> {
+    let mut fused: HashMap<String, (f32, ScoreBreakdown)> = HashMap::new();
+    for (rank, (id, similarity)) in vector.iter().enumerate() {
+        let (score, entry) = fused.entry(id.clone()).or_default();
+        let share = config.vector_weight / (config.rrf_k + (rank + 1) as f32);
+        entry.vector_rank = Some(rank + 1);
+        entry.similarity = Some(*similarity);
+        entry.rrf += share;
+        *score += share * similarity.clamp(0.0, 1.0);
+    }
+    for (rank, (id, bm25, overlap)) in lexical.iter().enumerate() {
+        let (score, entry) = fused.entry(id.clone()).or_default();
+        let share = config.lexical_weight / (config.rrf_k + (rank + 1) as f32);
+        entry.lexical_rank = Some(rank + 1);
+        entry.bm25 = Some(*bm25);
+        entry.term_overlap = Some(*overlap);
+        entry.rrf += share;
+        *score += share * overlap;
+    }
+
+    let vector_weight = if use_vector { config.vector_weight } else { 0.0 };
+    let best = (vector_weight + config.lexical_weight) / (config.rrf_k + 1.0);
+    let mut ranked: Vec<_> = fused
+        .into_iter()
+        .map(|(id, (score, breakdown))| (id, if best > 0.0 { score / best } else { 0.0 }, breakdown))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+const MEMORY_COLUMNS: &str =
+    "id, text, source, tool, session_id, tags, created_at, updated_at, hit_count, importance, parent_id, chunk_index, chunk_start, chunk_end";
+
+fn read_memory_row(row: &rusqlite::Row) -> rusqlite::Result<SemanticMemory> {
+    let source: String = row.get(2)?;
+    let tags: String = row.get(5)?;
+    Ok(SemanticMemory {
+        id: row.get(0)?,
+        text: row.get(1)?,
+        metadata: MemoryMetadata {
+            source: MemorySource::parse(&source).unwrap_or_default(),
+            tool: row.get(3)?,
+            session_id: row.get(4)?,
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+        },
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        hit_count: row.get(8)?,
+        importance: row.get::<_, f64>(9)? as f32,
+        chunk: match row.get::<_, Option<String>>(10)? {
+            Some(parent_id) => Some(ChunkRef {
+                parent_id,
+                index: row.get(11)?,
+                start: row.get(12)?,
+                end: row.get(13)?,
+            }),
+            None => None,
+        },
+    })
+}
+
+fn read_reindex_job(conn: &Connection, generation: i64) -> Result<ReindexJob, String> {
+    conn.query_row(
+        "SELECT generation, embedder, status, total, started_at, finished_at, error,
+                CASE WHEN status = 'complete' THEN total
+                     ELSE (SELECT COUNT(*) FROM reindex_staging s WHERE s.generation = j.generation) END
+         FROM reindex_jobs j WHERE generation = ?1",
+        params![generation],
+        |row| {
+            Ok(ReindexJob {
+                generation: row.get(0)?,
+                embedder: row.get(1)?,
+                status: row.get(2)?,
+                total: row.get(3)?,
+                started_at: row.get(4)?,
+                finished_at: row.get(5)?,
+                error: row.get(6)?,
+                staged: row.get(7)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn stored_memory_ids(conn: &Connection) -> Result<HashSet<String>, String> {
+    let mut stmt = conn.prepare("SELECT id FROM semantic_memory").map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+// None (with a warning) when the row fails its checksum or can't be decoded.
+fn read_stored_vector(conn: &Connection, id: &str) -> Result<Option<StoredVector>, String> {
+    let (text, blob, checksum): (String, Vec<u8>, String) = conn
+        .query_row(
+            "SELECT text, vector, checksum FROM semantic_memory WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    if memory_checksum(&text, &blob) != checksum {
+        eprintln!("Semantic memory {} failed its checksum; not indexed", id);
+        return Ok(None);
+    }
+    match embedding::decode(&blob) {
+        Ok(stored) => Ok(Some(stored)),
+        Err(e) => {
+            eprintln!("Semantic memory {} has an unreadable vector ({}); not indexed", id, e);
+            Ok(None)
+        }
+    }
+}
+
+fn legacy_ids(dir: &str, extension: &str) -> Result<HashSet<String>, String> {
+    let mut ids = HashSet::new();
+    if !Path::new(dir).exists() {
+        return Ok(ids);
+    }
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) == Some(extension) {
+            ids.insert(path.file_stem().unwrap().to_string_lossy().to_string());
+        }
+    }
+    Ok(ids)
+}
+
+// Moves every file of `dir` into `target`, replacing files of the same
+// name from an earlier migration, then removes `dir`.
+fn merge_legacy_dir(dir: &str, target: &str) -> Result<(), String> {
+    if !Path::new(dir).exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(target).map_err(|e| e.to_string())?;
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let destination = Path::new(target).join(entry.file_name());
+        if let Err(e) = fs::rename(entry.path(), &destination) {
+            eprintln!("Could not move {} to {}: {}", entry.path().display(), destination.display(), e);
+        }
+    }
+    if let Err(e) = fs::remove_dir(dir) {
+        eprintln!("Could not remove {}: {}", dir, e);
+    }
+    Ok(())
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot_product / (norm_a * norm_b)
+    }
+}
+
+// Older trace memories only name their tool in the text
+// ("Action: .. Tool: .. Prompt: ..").
+fn backfill_trace_tools(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "UPDATE semantic_memory
+            SET tool = substr(text, instr(text, ' Tool: ') + 7, instr(text, ' Prompt: ') - instr(text, ' Tool: ') - 7)
+          WHERE tool IS NULL AND source = 'trace' AND text LIKE 'Action: % Tool: % Prompt: %'
+            AND instr(text, ' Tool: ') < instr(text, ' Prompt: ')",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Traces of git_agent and obsidian_agent calls were once stored as
+// git_commit / vault_note memories; they are traces like any other.
+fn relabel_agent_traces(conn: &Connection, importance: f32) -> Result<(), String> {
+    conn.execute(
+        "UPDATE semantic_memory SET source = 'trace', importance = ?1
+          WHERE source IN ('git_commit', 'vault_note') AND tool IN ('git_agent', 'obsidian_agent')
+            AND text LIKE 'Action: % Tool: % Prompt: %'",
+        params![importance],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// SQLite has no "ADD COLUMN IF NOT EXISTS", so check table_info first.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])
+            .map_err(|e| e.to_string())?;
     }
+    Ok(())
 }
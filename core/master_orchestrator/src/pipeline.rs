@@ -0,0 +1,260 @@
+use crate::estimator::{self, PlanEstimate, PlannedCall};
+use crate::executor::provider_config_json;
+use crate::memory_service::{AgentConfig, MemoryService, TraceMeta};
+use crate::planner::{execute_action, resolve_provider};
+use crate::self_correction::is_failure;
+use serde_json::{json, Map, Value};
+use shared_types::{ActionResponse, ActionResult, ActionSpec, AppConfig};
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Pipes: `/git log --since=yesterday | /llm summarize | /obsidian append Daily`
+// runs each segment as a direct agent call, skipping intent detection, and
+// feeds the previous step's `ActionResult.data` to the next as `input`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeSegment {
+    pub tool: String,
+    pub action: String,
+    pub args: Vec<String>,
+    // `--key=value` and bare `--flag` options
+    pub options: Map<String, Value>,
+}
+
+// Splits on `separator` outside single or double quotes.
+fn split_unquoted(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (None, c) if c == separator => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+// Whitespace-separated words; quotes group words and are removed.
+fn tokenize(segment: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_token = false;
+    for c in segment.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+// `/git` matches "git", "git_agent", or the only tool starting "git_".
+fn resolve_tool(command: &str, agents: &[AgentConfig]) -> Option<String> {
+    let command = command.to_lowercase();
+    let exact = [command.clone(), format!("{}_agent", command)];
+    if let Some(agent) = agents.iter().find(|a| exact.contains(&a.tool_name)) {
+        return Some(agent.tool_name.clone());
+    }
+    let prefix = format!("{}_", command);
+    let mut matches = agents.iter().filter(|a| a.tool_name.starts_with(&prefix));
+    match (matches.next(), matches.next()) {
+        (Some(agent), None) => Some(agent.tool_name.clone()),
+        _ => None,
+    }
+}
+
+fn parse_segment(segment: &str, agents: &[AgentConfig]) -> Result<PipeSegment, String> {
+    let tokens = tokenize(segment);
+    let command = tokens
+        .first()
+        .and_then(|t| t.strip_prefix('/'))
+        .ok_or_else(|| format!("Pipe segment '{}' must start with /<agent>", segment.trim()))?;
+    let tool = resolve_tool(command, agents).ok_or_else(|| format!("No registered agent for '/{}'", command))?;
+
+    let mut action = None;
+    let mut args = Vec::new();
+    let mut options = Map::new();
+    for token in &tokens[1..] {
+        if let Some(option) = token.strip_prefix("--") {
+            match option.split_once('=') {
+                Some((key, value)) => options.insert(key.to_string(), Value::String(value.to_string())),
+                None => options.insert(option.to_string(), Value::Bool(true)),
+            };
+        } else if action.is_none() {
+            action = Some(token.clone());
+        } else {
+            args.push(token.clone());
+        }
+    }
+
+    Ok(PipeSegment {
+        tool,
+        action: action.unwrap_or_else(|| "execute".to_string()),
+        args,
+        options,
+    })
+}
+
+// None when the message isn't pipe syntax (its first command is not an
+// agent); Some(Err) when it is but a later segment is malformed.
+pub fn parse(message: &str, agents: &[AgentConfig]) -> Option<Result<Vec<PipeSegment>, String>> {
+    let message = message.trim();
+    let first = message.strip_prefix('/')?;
+    let command: String = first.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    resolve_tool(&command, agents)?;
+
+    Some(split_unquoted(message, '|').iter().map(|s| parse_segment(s, agents)).collect())
+}
+
+fn build_action(segment: &PipeSegment, input: Option<&str>, app_config: &AppConfig) -> ActionSpec {
+    let mut payload = segment.options.clone();
+    payload.insert("args".to_string(), json!(segment.args));
+
+    // The LLM agent only reads `prompt`: the whole instruction plus input.
+    if let Some((name, config)) = resolve_provider(&segment.tool, app_config) {
+        let instruction = std::iter::once(segment.action.as_str())
+            .chain(segment.args.iter().map(|a| a.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let prompt = match input {
+            Some(input) => format!("{}\n\n{}", instruction, input),
+            None => instruction,
+        };
+        payload.insert("prompt".to_string(), Value::String(prompt));
+        payload.insert("config".to_string(), provider_config_json(&name, &config));
+    } else {
+        payload.insert("prompt".to_string(), Value::String(segment.args.join(" ")));
+        if let Some(input) = input {
+            payload.insert("input".to_string(), Value::String(input.to_string()));
+        }
+    }
+
+    ActionSpec {
+        tool: segment.tool.clone(),
+        action: segment.action.clone(),
+        context: String::new(),
+        payload: Value::Object(payload),
+    }
+}
+
+// Each step's input is estimated from the previous step's expected result.
+pub async fn estimate(segments: &[PipeSegment], memory_service: &MemoryService, app_config: &AppConfig) -> PlanEstimate {
+    let calls = segments
+        .iter()
+        .map(|s| PlannedCall::from_action(&build_action(s, None, app_config), app_config))
+        .collect();
+    estimator::estimate_sequence(calls, memory_service, app_config).await
+}
+
+// Runs the segments in order, stopping at the first failure. The returned
+// response is the last step's, with every step listed in `metadata.pipeline`.
+pub async fn run(
+    segments: Vec<PipeSegment>,
+    memory_service: Arc<MemoryService>,
+    app_config: &AppConfig,
+    base_meta: TraceMeta,
+) -> Result<ActionResponse, String> {
+    if app_config.estimation.enabled {
+        estimator::enforce(&estimate(&segments, &memory_service, app_config).await, app_config)?;
+    }
+
+    let pipeline_id = Uuid::new_v4().to_string();
+    let mut steps = Vec::new();
+    let mut input: Option<String> = None;
+    let mut last: Option<ActionResponse> = None;
+
+    for (index, segment) in segments.iter().enumerate() {
+        let meta = TraceMeta {
+            pipeline_id: Some(pipeline_id.clone()),
+            pipeline_step: Some(index as i64 + 1),
+            ..base_meta.clone()
+        };
+        let action = build_action(segment, input.as_deref(), app_config);
+        let (response, error) = match execute_action(&action, memory_service.clone(), meta).await {
+            Ok(response) if is_failure(&response) => {
+                let error = response
+                    .error
+                    .clone()
+                    .or_else(|| response.result.as_ref().map(|r| r.data.clone()))
+                    .unwrap_or_default();
+                (Some(response), Some(error))
+            }
+            Ok(response) => (Some(response), None),
+            // The agent never answered, so there is no trace to point at
+            Err(e) => (None, Some(e)),
+        };
+
+        steps.push(json!({
+            "step": index + 1,
+            "tool": segment.tool,
+            "action": segment.action,
+            "trace_id": response.as_ref().map(|r| r.request_id.to_string()),
+            "status": if error.is_some() { "error" } else { "success" },
+            "error": error,
+        }));
+
+        let response = match (response, error) {
+            (Some(response), None) => response,
+            (response, error) => {
+                return Ok(ActionResponse {
+                    request_id: response.as_ref().map(|r| r.request_id).unwrap_or_else(Uuid::new_v4),
+                    status: "error".to_string(),
+                    code: response.as_ref().map(|r| r.code).filter(|c| *c != 0).unwrap_or(1),
+                    result: Some(ActionResult {
+                        output_type: "error".to_string(),
+                        data: format!(
+                            "Pipe step {} (/{} {}) failed: {}",
+                            index + 1,
+                            segment.tool,
+                            segment.action,
+                            error.unwrap_or_default()
+                        ),
+                        metadata: Some(json!({ "pipeline": { "id": pipeline_id, "steps": steps } })),
+                    }),
+                    error: None,
+                });
+            }
+        };
+
+        input = response.result.as_ref().map(|r| r.data.clone());
+        last = Some(response);
+    }
+
+    let mut response = last.ok_or("Empty pipe")?;
+    if let Some(result) = response.result.as_mut() {
+        let mut metadata = result.metadata.take().unwrap_or_else(|| json!({}));
+        if let Some(obj) = metadata.as_object_mut() {
+            obj.insert("pipeline".to_string(), json!({ "id": pipeline_id, "steps": steps }));
+        }
+        result.metadata = Some(metadata);
+    }
+    Ok(response)
+}
@@ -1,108 +1,286 @@
-use crate::memory_service::MemoryService;
-use crate::executor::execute_agent;
-use shared_types::{ActionRequest, ActionResponse, Payload, AppConfig};
+use crate::memory_service::{AgentConfig, MemoryService, TraceMeta};
+use crate::executor::{execute_agent, provider_config_json};
+use crate::context_builder::{build_context, AssembledContext, ContextQuery};
+use crate::router::{CompositeRouter, RoutingDecision, RoutingOutcome};
+use crate::entity_extractor::extract_entities;
+use crate::fact_extractor::spawn_fact_extraction;
+use crate::memory_commands;
+use crate::self_correction;
+use crate::fan_out;
+use crate::workflow;
+use crate::pipeline;
+use crate::goals::spawn_goal_linking;
+use crate::estimator::{self, PlannedCall};
+use crate::prompt_templates::{render as render_prompt, RenderedPrompt};
+use shared_types::{ActionRequest, ActionResponse, ActionSpec, Payload, AppConfig, ProviderConfig};
 use std::sync::Arc;
+use std::time::Instant;
 use uuid::Uuid;
 use serde_json::json;
 
-// Planner's main job: turn user intent into an ActionRequest
+// Planner's main job: turn user intent into an ActionRequest. With
+// `dry_run` (or a `/estimate` prefix) the plan is estimated, not executed.
+// `template` overrides the session's prompt template.
 pub async fn plan_and_execute(
-    user_message: String, 
+    user_message: String,
+    session_id: Option<String>,
+    template: Option<String>,
+    dry_run: bool,
     memory_service: Arc<MemoryService>,
-    app_config: Arc<AppConfig>
+    app_config: Arc<AppConfig>,
+    mut trace_meta: TraceMeta,
 ) -> Result<ActionResponse, String> {
-    // 1. Check Agent Registry
-    let active_agents = memory_service.get_active_agents().await.map_err(|e| format!("Memory Error: {}", e))?;
-    
-    // 2. Simple Intent Detection (Keyword-based for V1)
-    let target_tool = if user_message.to_lowercase().contains("git") || user_message.to_lowercase().contains("commit") {
-        "git_agent" 
-    } else {
-        "llm_router_agent" // Fallback to the future LLM planning agent
+    let (user_message, dry_run) = match user_message.trim_start().strip_prefix("/estimate ") {
+        Some(rest) => (rest.trim().to_string(), true),
+        None => (user_message, dry_run),
     };
-    
-    // 3. Agent Validation Check (The Gatekeeper)
-    if !active_agents.iter().any(|a| a.tool_name == target_tool) {
-        return Err(format!("Error: Agent '{}' is not registered or active.", target_tool));
+    if trace_meta.session_id.is_none() {
+        trace_meta.session_id = session_id.clone();
     }
 
-    // --- NEW: Context Retrieval ---
-    let mut context_str = String::new();
-    
-    // A. Structured Retrieval (Simple keyword match for now)
-    // In a real system, we'd extract entities. Here we just try the whole message or keywords.
-    if let Ok(facts) = memory_service.retrieve_structured_context(&user_message).await {
-        if !facts.is_empty() {
-            context_str.push_str("\n[Structured Memory]:\n");
-            for fact in facts {
-                context_str.push_str(&format!("- {}\n", fact));
-            }
+    // 0. Explicit memory commands are handled directly, without an agent
+    if let Some(command) = memory_commands::parse(&user_message) {
+        if dry_run {
+            let estimate = estimator::estimate_sequence(Vec::new(), &memory_service, &app_config).await;
+            return Ok(estimator::to_response(&estimate));
+        }
+        return memory_commands::execute(command, &memory_service, session_id.as_deref()).await;
+    }
+    if let Some(invocation) = workflow::parse_command(&user_message) {
+        if dry_run {
+            let estimate = workflow::estimate(&invocation.name, invocation.inputs, &memory_service, &app_config).await?;
+            return Ok(estimator::to_response(&estimate));
         }
+        let run = workflow::run(&invocation.name, invocation.inputs, memory_service, app_config, trace_meta).await?;
+        return Ok(workflow::to_response(&run));
     }
 
-    // B. Semantic Retrieval
-    if let Ok(memories) = memory_service.retrieve_semantic_context(&user_message, 3).await {
-        if !memories.is_empty() {
-            context_str.push_str("\n[Semantic Memory]:\n");
-            for mem in memories {
-                context_str.push_str(&format!("- {}\n", mem));
+    // Pipe syntax (`/git log | /llm summarize`) calls agents directly
+    let active_agents = memory_service.get_active_agents().await.map_err(|e| format!("Memory Error: {}", e))?;
+    if let Some(segments) = pipeline::parse(&user_message, &active_agents) {
+        let segments = segments?;
+        if dry_run {
+            let estimate = pipeline::estimate(&segments, &memory_service, &app_config).await;
+            return Ok(estimator::to_response(&estimate));
+        }
+        return pipeline::run(segments, memory_service, &app_config, trace_meta).await;
+    }
+
+    // 1. Routing (strategies composed from [routing] in config)
+    let routing = route(&user_message, &active_agents, &memory_service, &app_config).await?;
+    let decision = routing.decision.clone();
+    println!("Routed to {} via {} (score {:.2})", decision.tool, decision.router, decision.score);
+
+    // 2. Agent Validation Check (The Gatekeeper)
+    if !active_agents.iter().any(|a| a.tool_name == decision.tool) {
+        return Err(format!("Error: Agent '{}' is not registered or active.", decision.tool));
+    }
+
+    // 3. Resolve the LLM provider (needed for prompt injection and token budgeting)
+    let provider = resolve_provider(&decision.tool, &app_config);
+
+    // 4. Context Retrieval (ranked, deduplicated and token-budgeted)
+    let entities = extract_entities(&memory_service, &user_message, &app_config).await;
+    let query = ContextQuery {
+        memory_service: &memory_service,
+        user_message: &user_message,
+        entities: &entities,
+        session_id: session_id.as_deref(),
+        app_config: &app_config,
+    };
+    let assembled = build_context(
+        &query,
+        provider.as_ref().map(|(name, _)| name.as_str()),
+        provider.as_ref().map(|(_, c)| c.model_name.as_str()).unwrap_or_default(),
+    )
+    .await;
+
+    // 5. Render the prompt and build the ActionRequest (The Universal Contract)
+    let template_name = match (template, &session_id) {
+        (Some(template), _) => Some(template),
+        (None, Some(id)) => memory_service.get_session_template(id).await.unwrap_or(None),
+        (None, None) => None,
+    };
+
+    // 5b. Several agents accepted: dispatch to all of them in parallel
+    if !routing.fan_out.is_empty() {
+        let mut requests = Vec::new();
+        for target in &routing.fan_out {
+            let provider = resolve_provider(&target.tool, &app_config);
+            let rendered = render_prompt(&app_config.prompts, template_name.as_deref(), &target.tool, &user_message, &assembled)?;
+            requests.push(build_request(target, &user_message, &assembled, &rendered, provider.as_ref()));
+        }
+        let trace_ids: Vec<String> = requests.iter().map(|r| r.request_id.to_string()).collect();
+
+        if dry_run || app_config.estimation.enabled {
+            let retries = self_correction::retries(&app_config);
+            let calls = requests.iter().map(|r| PlannedCall::from_request(r, &app_config).with_retries(retries)).collect();
+            let estimate = estimator::estimate_parallel(calls, &memory_service, &app_config).await;
+            if dry_run {
+                return Ok(estimator::to_response(&estimate));
             }
+            estimator::enforce(&estimate, &app_config)?;
         }
+
+        trace_meta.context_report = Some(assembled.report);
+        trace_meta.routing = Some(routing);
+        let response = fan_out::dispatch(&user_message, requests, memory_service.clone(), &app_config, &trace_meta).await;
+
+        spawn_goal_linking(memory_service.clone(), app_config.clone(), user_message.clone(), trace_ids.clone());
+        extract_facts(&response, trace_ids[0].clone(), user_message, &memory_service, &app_config);
+        return Ok(response);
     }
 
-    // 4. Prepare Payload
-    let mut payload_json = json!({"prompt": user_message});
-
-    // Inject LLM Config if target is llm_router_agent
-    if target_tool == "llm_router_agent" {
-        let default_provider = &app_config.llm.default_provider;
-        let provider_config = match default_provider.as_str() {
-            "openrouter" => &app_config.llm.openrouter,
-            "gemini" => &app_config.llm.gemini,
-            "grok" => &app_config.llm.grok,
-            "openai" => &app_config.llm.openai,
-            "anthropic" => &app_config.llm.anthropic,
-            "ollama" => &app_config.llm.ollama,
-            "lmstudio" => &app_config.llm.lmstudio,
-            _ => &None,
-        };
-
-        if let Some(config) = provider_config {
-            // Append context to the prompt for the LLM
-            let final_prompt = if !context_str.is_empty() {
-                format!("{}\n\nContext:\n{}", user_message, context_str)
-            } else {
-                user_message.clone()
-            };
-
-            payload_json = json!({
-                "prompt": final_prompt,
-                "config": {
-                    "provider": default_provider,
-                    "api_key": config.api_key,
-                    "base_url": config.base_url,
-                    "model_name": config.model_name
-                }
-            });
+    let rendered = render_prompt(&app_config.prompts, template_name.as_deref(), &decision.tool, &user_message, &assembled)?;
+    let request = build_request(&decision, &user_message, &assembled, &rendered, provider.as_ref());
+
+    // 5c. Cost and latency estimate: returned for dry runs, else checked
+    // against the configured ceilings
+    if dry_run || app_config.estimation.enabled {
+        let calls = vec![PlannedCall::from_request(&request, &app_config).with_retries(self_correction::retries(&app_config))];
+        let estimate = estimator::estimate_sequence(calls, &memory_service, &app_config).await;
+        if dry_run {
+            return Ok(estimator::to_response(&estimate));
         }
+        estimator::enforce(&estimate, &app_config)?;
     }
-    
-    // 5. Create the ActionRequest (The Universal Contract)
-    let request = ActionRequest {
+
+    // 6. Execute and log
+    trace_meta.context_report = Some(assembled.report);
+    trace_meta.routing = Some(routing);
+    let response = match execute_and_log(&request, &memory_service, &trace_meta).await {
+        Ok(response) => response,
+        // An agent that could not run is a failed attempt like any other
+        Err(e) if app_config.self_correction.enabled => self_correction::failed_response(&request, e),
+        Err(e) => return Err(e),
+    };
+
+    // 7. Optional self-correction: re-plan and retry failed calls
+    let (request, response) = if app_config.self_correction.enabled && self_correction::is_failure(&response) {
+        self_correction::correct(&user_message, request, response, &memory_service, &app_config, &trace_meta).await
+    } else {
+        (request, response)
+    };
+
+    let trace_id = request.request_id.to_string();
+    spawn_goal_linking(memory_service.clone(), app_config.clone(), user_message.clone(), vec![trace_id.clone()]);
+    extract_facts(&response, trace_id, user_message, &memory_service, &app_config);
+    Ok(response)
+}
+
+// Post-response: mine the exchange for knowledge-graph facts
+fn extract_facts(
+    response: &ActionResponse,
+    trace_id: String,
+    user_message: String,
+    memory_service: &Arc<MemoryService>,
+    app_config: &Arc<AppConfig>,
+) {
+    let agent_result = response
+        .result
+        .as_ref()
+        .filter(|r| r.output_type != "error")
+        .map(|r| r.data.clone());
+    spawn_fact_extraction(memory_service.clone(), app_config.clone(), trace_id, user_message, agent_result);
+}
+
+pub async fn route(
+    message: &str,
+    agents: &[AgentConfig],
+    memory_service: &MemoryService,
+    app_config: &Arc<AppConfig>,
+) -> Result<RoutingOutcome, String> {
+    let router = CompositeRouter::from_config(app_config, &memory_service.embedder())?;
+    Ok(router.decide(message, agents).await)
+}
+
+// Only the LLM router receives provider settings.
+pub fn resolve_provider(tool: &str, app_config: &AppConfig) -> Option<(String, ProviderConfig)> {
+    if tool != "llm_router_agent" {
+        return None;
+    }
+    let name = app_config.llm.default_provider.clone();
+    app_config.llm.provider(&name).cloned().map(|config| (name, config))
+}
+
+fn build_request(
+    decision: &RoutingDecision,
+    user_message: &str,
+    assembled: &AssembledContext,
+    rendered: &RenderedPrompt,
+    provider: Option<&(String, ProviderConfig)>,
+) -> ActionRequest {
+    let (payload_json, context) = match provider {
+        Some((name, config)) => (
+            json!({
+                "prompt": rendered.user,
+                "system": rendered.system,
+                "template": rendered.template,
+                "config": provider_config_json(name, config)
+            }),
+            // Already part of the prompt; don't send it twice.
+            String::new(),
+        ),
+        None => {
+            let prompt = if rendered.tool_specific { rendered.user.as_str() } else { user_message };
+            (json!({"prompt": prompt}), assembled.text.clone())
+        }
+    };
+
+    ActionRequest {
         request_id: Uuid::new_v4(),
-        tool: target_tool.to_string(),
-        action: "execute".to_string(), // Default action for now, agents can parse args
-        context: context_str, // Also pass context in the dedicated field
+        tool: decision.tool.clone(),
+        action: decision.action.clone(),
+        context,
         payload: Payload(payload_json),
+    }
+}
+
+async fn execute_and_log(
+    request: &ActionRequest,
+    memory_service: &MemoryService,
+    trace_meta: &TraceMeta,
+) -> Result<ActionResponse, String> {
+    let started = Instant::now();
+    let (response, error) = match execute_agent(&request.tool, request) {
+        Ok(response) => (response, None),
+        // Traced as well, so retries and scheduled runs can point at it
+        Err(e) => (self_correction::failed_response(request, e.clone()), Some(e)),
+    };
+    let trace_meta = TraceMeta {
+        latency_ms: Some(started.elapsed().as_millis() as i64),
+        ..trace_meta.clone()
     };
 
-    // 6. Execute the Agent
-    let response = execute_agent(target_tool, &request)?;
-    
     // Log action trace (This will now also index the action semantically!)
-    if let Err(e) = memory_service.log_action_trace(&request, &response).await {
+    if let Err(e) = memory_service.log_action_trace(request, &response, &trace_meta).await {
         eprintln!("Failed to log action trace: {}", e);
     }
+    match error {
+        Some(e) => Err(e),
+        None => Ok(response),
+    }
+}
 
-    Ok(response)
+// Runs an explicit agent call, skipping intent detection and context
+// retrieval, with the same registry check and trace logging as chat.
+pub async fn execute_action(
+    action: &ActionSpec,
+    memory_service: Arc<MemoryService>,
+    trace_meta: TraceMeta,
+) -> Result<ActionResponse, String> {
+    let active_agents = memory_service.get_active_agents().await.map_err(|e| format!("Memory Error: {}", e))?;
+    if !active_agents.iter().any(|a| a.tool_name == action.tool) {
+        return Err(format!("Error: Agent '{}' is not registered or active.", action.tool));
+    }
+
+    let request = ActionRequest {
+        request_id: Uuid::new_v4(),
+        tool: action.tool.clone(),
+        action: action.action.clone(),
+        context: action.context.clone(),
+        payload: Payload(action.payload.clone()),
+    };
+
+    execute_and_log(&request, &memory_service, &trace_meta).await
 }
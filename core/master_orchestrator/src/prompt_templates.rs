@@ -0,0 +1,99 @@
+use crate::context_builder::AssembledContext;
+use minijinja::{context, Environment};
+use shared_types::{PromptConfig, PromptTemplate};
+
+// Prompt Templates: renders the system prompt and user prompt for a request
+// from a named template in config, falling back to built-in defaults.
+
+const DEFAULT_PERSONA: &str = "a personal digital twin that helps its owner with their projects, notes and code";
+
+const DEFAULT_SYSTEM: &str = "You are {{ persona }}. Today is {{ date }}. \
+Use the provided context when it is relevant and say so when you don't know something.";
+
+// Mirrors the section layout produced by the context builder.
+const DEFAULT_CONTEXT: &str = "{% if goals %}\n[Active Goals]:\n{% for g in goals %}- {{ g }}\n{% endfor %}{% endif %}\
+{% if facts %}\n[Structured Memory]:\n{% for f in facts %}- {{ f }}\n{% endfor %}{% endif %}\
+{% if memories %}\n[Semantic Memory]:\n{% for m in memories %}- {{ m }}\n{% endfor %}{% endif %}\
+{% if history %}\n[Session History]:\n{% for h in history %}- {{ h }}\n{% endfor %}{% endif %}";
+
+const DEFAULT_USER: &str = "{{ user_message }}{% if memory_context %}\n\nContext:\n{{ memory_context }}{% endif %}";
+
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub template: String,
+    pub system: String,
+    pub user: String,
+    // True when `user` came from a per-tool template.
+    pub tool_specific: bool,
+}
+
+fn or_default<'a>(value: &'a str, default: &'a str) -> &'a str {
+    if value.trim().is_empty() {
+        default
+    } else {
+        value
+    }
+}
+
+// Unknown template names fall back to the configured default, then to the
+// built-in template.
+pub fn resolve<'a>(config: &'a PromptConfig, requested: Option<&'a str>) -> (String, Option<&'a PromptTemplate>) {
+    if let Some(name) = requested {
+        if let Some(template) = config.templates.get(name) {
+            return (name.to_string(), Some(template));
+        }
+    }
+    let name = config.default_template.clone();
+    let template = config.templates.get(&name);
+    (name, template)
+}
+
+pub fn render(
+    config: &PromptConfig,
+    requested: Option<&str>,
+    tool: &str,
+    user_message: &str,
+    assembled: &AssembledContext,
+) -> Result<RenderedPrompt, String> {
+    let (name, template) = resolve(config, requested);
+    let empty = PromptTemplate::default();
+    let template = template.unwrap_or(&empty);
+
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    let render_one = |source: &str, ctx: minijinja::Value| -> Result<String, String> {
+        env.render_str(source, ctx).map_err(|e| format!("Template '{}' failed to render: {}", name, e))
+    };
+
+    let date = chrono::Local::now().format("%A, %Y-%m-%d").to_string();
+    let base = context! {
+        user_message => user_message,
+        tool => tool,
+        date => date,
+        goals => assembled.goals,
+        facts => assembled.facts,
+        memories => assembled.memories,
+        history => assembled.history,
+    };
+
+    let persona = render_one(or_default(&template.persona, DEFAULT_PERSONA), base.clone())?;
+    let memory_context = render_one(or_default(&template.context, DEFAULT_CONTEXT), base.clone())?;
+
+    let vars = context! {
+        persona => persona,
+        memory_context => memory_context,
+        ..base
+    };
+
+    let tool_template = template.tools.get(tool);
+    let user_source = tool_template
+        .map(|s| s.as_str())
+        .unwrap_or_else(|| or_default(&template.user, DEFAULT_USER));
+
+    Ok(RenderedPrompt {
+        tool_specific: tool_template.is_some(),
+        system: render_one(or_default(&template.system, DEFAULT_SYSTEM), vars.clone())?,
+        user: render_one(user_source, vars)?,
+        template: name,
+    })
+}
@@ -0,0 +1,134 @@
+use crate::embedding::EmbedderId;
+use crate::memory_service::cosine_similarity;
+use crate::quantize::Quantization;
+use crate::vector_index::VectorIndex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Quantization Report: builds the index once per quantization over the
+// stored vectors and compares its top-k, before and after re-scoring, with
+// an exact search over the full-precision vectors. A sample of the stored
+// vectors serves as queries, each excluding itself from its results. A
+// result counts as found if it is at least as similar as the exact k-th,
+// so ties at the cut-off don't count as misses.
+// Re-scoring reads vectors from memory here, so query times leave out the
+// database reads the live search makes.
+
+#[derive(Serialize, Debug)]
+pub struct ModeReport {
+    pub quantization: String,
+    pub vector_bytes: usize,
+    pub bytes_per_vector: f32,
+    pub build_ms: f64,
+    // Share of the exact top-k in the index's own top-k
+    pub recall: f32,
+    // ... and in the top-k after re-scoring k * oversample candidates
+    pub rescored_recall: f32,
+    pub mean_query_us: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuantizationReport {
+    pub embedder: String,
+    pub memories: usize,
+    pub queries: usize,
+    pub k: usize,
+    pub oversample: usize,
+    pub exact_mean_query_us: f64,
+    pub modes: Vec<ModeReport>,
+}
+
+// Best `k` by similarity, leaving out `skip`.
+fn top_k(scored: impl IntoIterator<Item = (String, f32)>, skip: &str, k: usize) -> Vec<(String, f32)> {
+    let mut scored: Vec<(String, f32)> = scored.into_iter().filter(|(id, _)| id != skip).collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(k);
+    scored
+}
+
+pub fn evaluate(
+    embedder: &EmbedderId,
+    vectors: &[(String, Vec<f32>)],
+    k: usize,
+    queries: usize,
+    oversample: usize,
+) -> Result<QuantizationReport, String> {
+    if vectors.len() < 2 {
+        return Err(format!("Need at least 2 memories from {} to compare, found {}", embedder, vectors.len()));
+    }
+    let k = k.clamp(1, vectors.len() - 1);
+    let oversample = oversample.max(1);
+    let step = (vectors.len() / queries.max(1)).max(1);
+    let sample: Vec<&(String, Vec<f32>)> = vectors.iter().step_by(step).take(queries.max(1)).collect();
+    let full: HashMap<&str, &Vec<f32>> = vectors.iter().map(|(id, v)| (id.as_str(), v)).collect();
+
+    // Exact search: every vector, full precision. Keeps each query's k-th
+    // best similarity.
+    let started = Instant::now();
+    let cutoffs: Vec<f32> = sample
+        .iter()
+        .map(|(query_id, query)| {
+            let scored = vectors.iter().map(|(id, v)| (id.clone(), cosine_similarity(query, v)));
+            top_k(scored, query_id, k).last().map_or(f32::MIN, |(_, sim)| *sim)
+        })
+        .collect();
+    let exact_mean_query_us = started.elapsed().as_secs_f64() * 1e6 / sample.len() as f64;
+    let expected = (sample.len() * k) as f32;
+    let found_in = |query: &[f32], results: &[(String, f32)], cutoff: f32| {
+        results
+            .iter()
+            .filter(|(id, _)| cosine_similarity(query, full[id.as_str()]) >= cutoff - 1e-6)
+            .count()
+    };
+
+    let label = embedder.to_string();
+    let mut modes = Vec::new();
+    for quantization in Quantization::ALL {
+        let started = Instant::now();
+        let index = VectorIndex::build(&label, embedder.dim, quantization, vectors.iter().cloned());
+        let build_ms = started.elapsed().as_secs_f64() * 1e3;
+
+        let pool = if quantization == Quantization::None { k + 1 } else { (k + 1) * oversample };
+        let (mut found, mut rescored_found) = (0, 0);
+        let mut elapsed = Duration::ZERO;
+        for ((query_id, query), cutoff) in sample.iter().zip(&cutoffs) {
+            let query_started = Instant::now();
+            let candidates = index.search(query, pool);
+            let approximate = top_k(candidates.iter().cloned(), query_id, k);
+            let rescored = if quantization == Quantization::None {
+                approximate.clone()
+            } else {
+                let scored = candidates.into_iter().map(|(id, _)| {
+                    let similarity = cosine_similarity(query, full[id.as_str()]);
+                    (id, similarity)
+                });
+                top_k(scored, query_id, k)
+            };
+            elapsed += query_started.elapsed();
+            found += found_in(query, &approximate, *cutoff);
+            rescored_found += found_in(query, &rescored, *cutoff);
+        }
+        let mean_query_us = elapsed.as_secs_f64() * 1e6 / sample.len() as f64;
+
+        modes.push(ModeReport {
+            quantization: quantization.as_str().to_string(),
+            vector_bytes: index.vector_bytes(),
+            bytes_per_vector: index.vector_bytes() as f32 / index.len().max(1) as f32,
+            build_ms,
+            recall: found as f32 / expected,
+            rescored_recall: rescored_found as f32 / expected,
+            mean_query_us,
+        });
+    }
+
+    Ok(QuantizationReport {
+        embedder: label,
+        memories: vectors.len(),
+        queries: sample.len(),
+        k,
+        oversample,
+        exact_mean_query_us,
+        modes,
+    })
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+// Compact encodings for the vectors held by the in-memory index. Int8
+// keeps one byte per dimension plus a per-vector scale (symmetric, so the
+// largest component maps to ±127); binary keeps only the sign of each
+// dimension, 64 per word, and compares by Hamming distance. Both only
+// approximate cosine similarity, so callers re-score the best candidates
+// against the full-precision vectors stored in the database.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    #[default]
+    None,
+    Int8,
+    Binary,
+}
+
+impl Quantization {
+    pub const ALL: [Quantization; 3] = [Quantization::None, Quantization::Int8, Quantization::Binary];
+
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "none" => Ok(Self::None),
+            "int8" => Ok(Self::Int8),
+            "binary" => Ok(Self::Binary),
+            other => Err(format!("Unknown quantization '{}' (expected 'none', 'int8' or 'binary')", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Int8 => "int8",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+// A vector as the index stores it. Untagged so index files written before
+// quantization (plain f32 arrays) still load.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Code {
+    Full(Vec<f32>),
+    Int8 { scale: f32, values: Vec<i8> },
+    Binary { bits: Vec<u64> },
+}
+
+impl Code {
+    pub fn encode(vector: Vec<f32>, quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::None => Self::Full(vector),
+            Quantization::Int8 => {
+                let max = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+                let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                Self::Int8 {
+                    scale,
+                    values: vector.iter().map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8).collect(),
+                }
+            }
+            Quantization::Binary => Self::Binary { bits: sign_bits(&vector) },
+        }
+    }
+
+    // Approximate f32 vector; a binary code becomes ±1 per dimension, which
+    // has the same cosine similarities as its bits.
+    pub fn decode(&self, dim: usize) -> Vec<f32> {
+        match self {
+            Self::Full(values) => values.clone(),
+            Self::Int8 { scale, values } => values.iter().map(|v| *v as f32 * scale).collect(),
+            Self::Binary { bits } => (0..dim)
+                .map(|i| if bits[i / 64] >> (i % 64) & 1 == 1 { 1.0 } else { -1.0 })
+                .collect(),
+        }
+    }
+
+    // Whether this is a `quantization` code of a `dim`-dimensional vector.
+    pub fn fits(&self, dim: usize, quantization: Quantization) -> bool {
+        match (self, quantization) {
+            (Self::Full(values), Quantization::None) => values.len() == dim,
+            (Self::Int8 { values, .. }, Quantization::Int8) => values.len() == dim,
+            (Self::Binary { bits }, Quantization::Binary) => bits.len() == dim.div_ceil(64),
+            _ => false,
+        }
+    }
+
+    // Bytes the vector data takes in memory.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Full(values) => values.len() * 4,
+            Self::Int8 { values, .. } => values.len() + 4,
+            Self::Binary { bits } => bits.len() * 8,
+        }
+    }
+}
+
+// Bit i is set when component i is positive.
+fn sign_bits(vector: &[f32]) -> Vec<u64> {
+    let mut bits = vec![0u64; vector.len().div_ceil(64)];
+    for (i, x) in vector.iter().enumerate() {
+        if *x > 0.0 {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+// A full-precision query, with whatever it needs to be compared against
+// codes of one quantization.
+pub struct Query<'a> {
+    values: &'a [f32],
+    norm: f32,
+    bits: Vec<u64>,
+}
+
+impl<'a> Query<'a> {
+    pub fn new(values: &'a [f32], quantization: Quantization) -> Self {
+        Self {
+            values,
+            norm: values.iter().map(|x| x * x).sum::<f32>().sqrt(),
+            bits: if quantization == Quantization::Binary { sign_bits(values) } else { Vec::new() },
+        }
+    }
+
+    // Cosine similarity, or an estimate of it for quantized codes. For
+    // binary codes that's 1 - 2 * (differing signs / dimensions).
+    pub fn similarity(&self, code: &Code) -> f32 {
+        match code {
+            Code::Full(values) => crate::memory_service::cosine_similarity(self.values, values),
+            Code::Int8 { values, .. } => {
+                // The scale cancels out of the cosine
+                let (mut dot, mut squares) = (0.0f32, 0.0f32);
+                for (q, v) in self.values.iter().zip(values) {
+                    let v = *v as f32;
+                    dot += q * v;
+                    squares += v * v;
+                }
+                if self.norm == 0.0 || squares == 0.0 {
+                    0.0
+                } else {
+                    dot / (self.norm * squares.sqrt())
+                }
+            }
+            Code::Binary { bits } => {
+                if self.values.is_empty() {
+                    return 0.0;
+                }
+                let differing: u32 = self.bits.iter().zip(bits).map(|(a, b)| (a ^ b).count_ones()).sum();
+                1.0 - 2.0 * differing as f32 / self.values.len() as f32
+            }
+        }
+    }
+}
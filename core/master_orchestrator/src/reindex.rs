@@ -0,0 +1,101 @@
+use crate::memory_service::{MemoryService, ReindexJob};
+use std::sync::Arc;
+
+// Re-embeds every stored memory with the current embedder, under a fresh
+// snapshot of its corpus statistics if it keeps any. Vectors are staged
+// under a new generation one batch at a time and only swapped into the
+// stored vectors once every memory has been staged, so an interrupted run
+// loses nothing and resumes from the last staged batch when started again
+// with the same embedder. Searches keep using the old snapshot until the
+// swap. The index only holds vectors from the configured embedder, though,
+// so after switching embedders, memories embedded by the old one are only
+// found by lexical search until the reindex finishes. One reindex runs at
+// a time, guarded by a lock row in the database that the server and the
+// CLI both take.
+
+// How often the server checks whether stored vectors need a reindex.
+const REFRESH_CHECK_MINUTES: u64 = 60;
+
+pub async fn run(memory_service: &MemoryService, batch_size: usize) -> Result<ReindexJob, String> {
+    let owner = memory_service.acquire_reindex_lock().await?;
+    let result = run_locked(memory_service, batch_size, &owner).await;
+    if let Err(e) = memory_service.release_reindex_lock(&owner).await {
+        eprintln!("Failed to release the reindex lock: {}", e);
+    }
+    result
+}
+
+// Starts the run in the background; fails straight away if one is already
+// running.
+pub async fn spawn(memory_service: Arc<MemoryService>, batch_size: usize) -> Result<(), String> {
+    let owner = memory_service.acquire_reindex_lock().await?;
+    tokio::spawn(async move {
+        if let Err(e) = run_locked(&memory_service, batch_size, &owner).await {
+            eprintln!("Reindex failed: {}", e);
+        }
+        if let Err(e) = memory_service.release_reindex_lock(&owner).await {
+            eprintln!("Failed to release the reindex lock: {}", e);
+        }
+    });
+    Ok(())
+}
+
+async fn run_locked(memory_service: &MemoryService, batch_size: usize, owner: &str) -> Result<ReindexJob, String> {
+    let (mut job, embedder) = memory_service.start_or_resume_reindex().await?;
+    let id = embedder.id();
+    let label = id.to_string();
+    if job.staged > 0 {
+        println!("Reindex: resuming generation {} at {}/{}", job.generation, job.staged, job.total);
+    } else {
+        println!("Reindex: generation {} with {} ({} memories)", job.generation, label, job.total);
+    }
+
+    loop {
+        memory_service.refresh_reindex_lock(owner).await?;
+        let rows = memory_service.reindex_pending(job.generation, batch_size.max(1)).await?;
+        if rows.is_empty() {
+            break;
+        }
+        let texts: Vec<String> = rows.iter().map(|(_, text)| text.clone()).collect();
+        let embedded = embedder.embed(&texts).await.and_then(|vectors| {
+            if vectors.len() == rows.len() {
+                Ok(vectors)
+            } else {
+                Err(format!("Embedder returned {} vectors for {} texts", vectors.len(), rows.len()))
+            }
+        });
+        let vectors = match embedded {
+            Ok(vectors) => vectors,
+            Err(e) => {
+                // Keep what's staged; the next run picks up from here
+                memory_service.fail_reindex(job.generation, &e).await?;
+                return Err(e);
+            }
+        };
+        job = memory_service.stage_reindex_batch(job.generation, &id, rows, vectors).await?;
+        println!("Reindex: {}/{}", job.staged, job.total);
+    }
+
+    let job = memory_service.finish_reindex(job.generation, embedder).await?;
+    println!("Reindex: generation {} complete", job.generation);
+    Ok(job)
+}
+
+// Reindexes in the background whenever stored vectors fall behind the
+// embedder (see MemoryService::reindex_due), checking at startup and then
+// every REFRESH_CHECK_MINUTES.
+pub fn spawn_refresh(memory_service: Arc<MemoryService>, batch_size: usize) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(REFRESH_CHECK_MINUTES * 60));
+        loop {
+            ticker.tick().await;
+            if !memory_service.reindex_due() || memory_service.reindex_locked().await.unwrap_or(true) {
+                continue;
+            }
+            println!("Reindex: stored vectors are behind {}, reindexing", memory_service.embedder_id());
+            if let Err(e) = run(&memory_service, batch_size).await {
+                eprintln!("Automatic reindex failed: {}", e);
+            }
+        }
+    });
+}
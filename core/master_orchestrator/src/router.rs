@@ -0,0 +1,440 @@
+use crate::embedder::{embed_one, Embedder};
+use crate::embedding::EmbedderId;
+use crate::executor::{call_llm_agent, extract_json_span};
+use crate::memory_service::{cosine_similarity, AgentConfig};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use shared_types::{AppConfig, RoutingConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Routing: pluggable strategies that map a user message to an agent. Each
+// router returns a scored decision (or none); `CompositeRouter` combines the
+// routers named in `[routing].strategy`.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoutingDecision {
+    pub tool: String,
+    pub action: String,
+    pub score: f32,
+    pub router: String,
+    pub reason: String,
+}
+
+// The chosen decision plus every candidate considered, for the trace.
+// `fan_out` lists the agents to dispatch to in parallel when more than one
+// was accepted.
+#[derive(Serialize, Debug, Clone)]
+pub struct RoutingOutcome {
+    pub decision: RoutingDecision,
+    pub candidates: Vec<RoutingDecision>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fan_out: Vec<RoutingDecision>,
+}
+
+#[async_trait]
+pub trait Router: Send + Sync {
+    fn name(&self) -> &str;
+    async fn route(&self, message: &str, agents: &[AgentConfig]) -> Option<RoutingDecision>;
+
+    // Every plausible agent, at most one decision each; used for fan-out.
+    async fn route_all(&self, message: &str, agents: &[AgentConfig]) -> Vec<RoutingDecision> {
+        self.route(message, agents).await.into_iter().collect()
+    }
+
+    // Acceptance threshold for this router's scores; `None` uses `min_score`.
+    fn threshold(&self) -> Option<f32> {
+        None
+    }
+}
+
+fn is_active(agents: &[AgentConfig], tool: &str) -> bool {
+    agents.iter().any(|a| a.tool_name == tool)
+}
+
+// --- Keyword Router ---
+
+pub struct KeywordRouter {
+    keywords: Vec<(String, Vec<String>)>,
+}
+
+impl KeywordRouter {
+    pub fn new(config: &RoutingConfig) -> Self {
+        let mut keywords: Vec<(String, Vec<String>)> = config
+            .keywords
+            .iter()
+            .map(|(tool, words)| (tool.clone(), words.iter().map(|w| w.to_lowercase()).collect()))
+            .collect();
+        // HashMap order is random; keep ties deterministic.
+        keywords.sort_by(|a, b| a.0.cmp(&b.0));
+        Self { keywords }
+    }
+}
+
+impl KeywordRouter {
+    fn decision(&self, tool: &str, hits: &[&str]) -> RoutingDecision {
+        RoutingDecision {
+            tool: tool.to_string(),
+            action: "execute".to_string(),
+            score: (0.6 + 0.1 * (hits.len() as f32 - 1.0)).min(0.9),
+            router: "keyword".to_string(),
+            reason: format!("matched keywords: {}", hits.join(", ")),
+        }
+    }
+}
+
+#[async_trait]
+impl Router for KeywordRouter {
+    fn name(&self) -> &str {
+        "keyword"
+    }
+
+    async fn route(&self, message: &str, agents: &[AgentConfig]) -> Option<RoutingDecision> {
+        // Most hits wins; ties go to the first tool in name order.
+        self.route_all(message, agents)
+            .await
+            .into_iter()
+            .fold(None, |best: Option<RoutingDecision>, d| match best {
+                Some(b) if b.score >= d.score => Some(b),
+                _ => Some(d),
+            })
+    }
+
+    async fn route_all(&self, message: &str, agents: &[AgentConfig]) -> Vec<RoutingDecision> {
+        // Whole-word matches only, so "git" doesn't fire on "digital".
+        let message = format!(" {} ", message.to_lowercase().split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" "));
+
+        self.keywords
+            .iter()
+            .filter(|(tool, _)| is_active(agents, tool))
+            .filter_map(|(tool, words)| {
+                let hits: Vec<&str> = words.iter().filter(|w| message.contains(&format!(" {} ", w))).map(|w| w.as_str()).collect();
+                (!hits.is_empty()).then(|| self.decision(tool, &hits))
+            })
+            .collect()
+    }
+}
+
+// --- Rule-Table Router ---
+
+pub struct RuleTableRouter {
+    rules: Vec<(Regex, shared_types::RoutingRule)>,
+}
+
+impl RuleTableRouter {
+    pub fn new(config: &RoutingConfig) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            let re = Regex::new(&rule.pattern).map_err(|e| format!("Invalid routing rule '{}': {}", rule.pattern, e))?;
+            rules.push((re, rule.clone()));
+        }
+        Ok(Self { rules })
+    }
+}
+
+#[async_trait]
+impl Router for RuleTableRouter {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    // First matching rule wins; rule order in config is priority order.
+    async fn route(&self, message: &str, agents: &[AgentConfig]) -> Option<RoutingDecision> {
+        self.rules
+            .iter()
+            .find(|(re, rule)| is_active(agents, &rule.tool) && re.is_match(message))
+            .map(|(_, rule)| RoutingDecision {
+                tool: rule.tool.clone(),
+                action: rule.action.clone(),
+                score: rule.score,
+                router: self.name().to_string(),
+                reason: format!("matched rule /{}/", rule.pattern),
+            })
+    }
+}
+
+// --- Embedding Router ---
+
+pub struct EmbeddingRouter {
+    app_config: Arc<AppConfig>,
+    embedder: Arc<dyn Embedder>,
+}
+
+// Utterances and their vectors, per (embedder name, agent), with the full
+// id they were embedded by. Reused until the agent's utterances change or
+// the embedder moves to a new version, e.g. a refreshed TF-IDF snapshot.
+type UtteranceVectors = Arc<Vec<(String, Vec<f32>)>>;
+type UtteranceCache = HashMap<(String, String), (EmbedderId, UtteranceVectors)>;
+
+fn utterance_cache() -> &'static Mutex<UtteranceCache> {
+    static CACHE: OnceLock<Mutex<UtteranceCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl EmbeddingRouter {
+    pub fn new(app_config: Arc<AppConfig>, embedder: Arc<dyn Embedder>) -> Self {
+        Self { app_config, embedder }
+    }
+
+    // The hash embedder is case- and punctuation-sensitive; normalise first.
+    fn normalize(text: &str) -> String {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Description plus manifest examples; each is matched separately.
+    fn utterances(&self, agent: &AgentConfig) -> Vec<String> {
+        let manifest = self.app_config.agents.get(&agent.tool_name);
+        let description = manifest
+            .and_then(|m| m.description.clone())
+            .unwrap_or_else(|| agent.description.clone());
+        let mut utterances = vec![description];
+        if let Some(m) = manifest {
+            utterances.extend(m.examples.iter().cloned());
+        }
+        utterances
+    }
+
+    async fn utterance_vectors(&self, agent: &AgentConfig) -> Result<UtteranceVectors, String> {
+        let utterances = self.utterances(agent);
+        let id = self.embedder.id();
+        let key = (id.name.clone(), agent.tool_name.clone());
+        if let Some((cached_id, cached)) = utterance_cache().lock().unwrap().get(&key) {
+            if *cached_id == id && cached.iter().map(|(u, _)| u).eq(utterances.iter()) {
+                return Ok(cached.clone());
+            }
+        }
+
+        let texts: Vec<String> = utterances.iter().map(|u| Self::normalize(u)).collect();
+        let vectors = self.embedder.embed(&texts).await?;
+        let entry: UtteranceVectors = Arc::new(utterances.into_iter().zip(vectors).collect());
+        utterance_cache().lock().unwrap().insert(key, (id, entry.clone()));
+        Ok(entry)
+    }
+}
+
+#[async_trait]
+impl Router for EmbeddingRouter {
+    fn name(&self) -> &str {
+        "embedding"
+    }
+
+    // Nearest utterance across all agents; always returns the best match so
+    // its score is logged even when it falls below the threshold.
+    async fn route(&self, message: &str, agents: &[AgentConfig]) -> Option<RoutingDecision> {
+        self.route_all(message, agents)
+            .await
+            .into_iter()
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    // Best utterance per agent, embedded with the configured embedder.
+    async fn route_all(&self, message: &str, agents: &[AgentConfig]) -> Vec<RoutingDecision> {
+        let query = match embed_one(self.embedder.as_ref(), &Self::normalize(message)).await {
+            Ok(query) => query,
+            Err(e) => {
+                eprintln!("Embedding router: {}", e);
+                return Vec::new();
+            }
+        };
+        let mut decisions = Vec::new();
+
+        for agent in agents {
+            let utterances = match self.utterance_vectors(agent).await {
+                Ok(utterances) => utterances,
+                Err(e) => {
+                    eprintln!("Embedding router: {} ({})", e, agent.tool_name);
+                    continue;
+                }
+            };
+            let best = utterances
+                .iter()
+                .map(|(u, vector)| (cosine_similarity(&query, vector), u))
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            if let Some((score, utterance)) = best.filter(|(score, _)| *score > 0.0) {
+                decisions.push(RoutingDecision {
+                    tool: agent.tool_name.clone(),
+                    action: "execute".to_string(),
+                    score,
+                    router: self.name().to_string(),
+                    reason: format!("nearest utterance: \"{}\"", utterance),
+                });
+            }
+        }
+        decisions
+    }
+
+    fn threshold(&self) -> Option<f32> {
+        Some(self.app_config.routing.embedding_threshold)
+    }
+}
+
+// --- LLM Router ---
+
+pub struct LlmRouter {
+    app_config: Arc<AppConfig>,
+}
+
+impl LlmRouter {
+    pub fn new(app_config: Arc<AppConfig>) -> Self {
+        Self { app_config }
+    }
+}
+
+#[derive(Deserialize)]
+struct LlmChoice {
+    tool: String,
+    #[serde(default)]
+    action: Option<String>,
+    #[serde(default)]
+    confidence: Option<f32>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[async_trait]
+impl Router for LlmRouter {
+    fn name(&self) -> &str {
+        "llm"
+    }
+
+    async fn route(&self, message: &str, agents: &[AgentConfig]) -> Option<RoutingDecision> {
+        let agent_list = agents
+            .iter()
+            .map(|a| format!("- {}: {}", a.tool_name, a.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = format!(
+            "Choose the agent best suited to handle the user's message.\n\nAgents:\n{}\n\nMessage: {}\n\n\
+             Respond with only a JSON object: {{\"tool\": ..., \"action\": \"execute\", \"confidence\": 0.0-1.0, \"reason\": ...}}",
+            agent_list, message
+        );
+
+        let data = call_llm_agent(&self.app_config, "route", &prompt).ok()?;
+        let choice: LlmChoice = serde_json::from_str(extract_json_span(&data, '{', '}')?).ok()?;
+        if !is_active(agents, &choice.tool) {
+            return None;
+        }
+
+        Some(RoutingDecision {
+            tool: choice.tool,
+            action: choice.action.unwrap_or_else(|| "execute".to_string()),
+            score: choice.confidence.unwrap_or(0.5).clamp(0.0, 1.0),
+            router: self.name().to_string(),
+            reason: choice.reason.unwrap_or_default(),
+        })
+    }
+}
+
+// --- Composition ---
+
+pub fn build_router(
+    name: &str,
+    app_config: &Arc<AppConfig>,
+    embedder: &Arc<dyn Embedder>,
+) -> Result<Box<dyn Router>, String> {
+    match name {
+        "keyword" => Ok(Box::new(KeywordRouter::new(&app_config.routing))),
+        "rules" => Ok(Box::new(RuleTableRouter::new(&app_config.routing)?)),
+        "embedding" => Ok(Box::new(EmbeddingRouter::new(app_config.clone(), embedder.clone()))),
+        "llm" => Ok(Box::new(LlmRouter::new(app_config.clone()))),
+        other => Err(format!("Unknown router '{}'", other)),
+    }
+}
+
+pub struct CompositeRouter {
+    routers: Vec<Box<dyn Router>>,
+    best_of: bool,
+    min_score: f32,
+    fallback_tool: String,
+    // 0 disables fan-out (single decision only).
+    max_fan_out: usize,
+}
+
+impl CompositeRouter {
+    pub fn from_config(app_config: &Arc<AppConfig>, embedder: &Arc<dyn Embedder>) -> Result<Self, String> {
+        let routing = &app_config.routing;
+        let routers = routing
+            .strategy
+            .iter()
+            .map(|name| build_router(name, app_config, embedder))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut router = Self::new(routers, routing);
+        if app_config.fan_out.enabled {
+            router.max_fan_out = app_config.fan_out.max_agents;
+        }
+        Ok(router)
+    }
+
+    pub fn new(routers: Vec<Box<dyn Router>>, routing: &RoutingConfig) -> Self {
+        Self {
+            routers,
+            best_of: routing.mode == "best",
+            min_score: routing.min_score,
+            fallback_tool: routing.fallback_tool.clone(),
+            max_fan_out: 0,
+        }
+    }
+
+    pub async fn decide(&self, message: &str, agents: &[AgentConfig]) -> RoutingOutcome {
+        // (decision, accepted) for every router that produced one
+        let mut considered: Vec<(RoutingDecision, bool)> = Vec::new();
+        for router in &self.routers {
+            let decisions = if self.max_fan_out > 1 {
+                router.route_all(message, agents).await
+            } else {
+                router.route(message, agents).await.into_iter().collect()
+            };
+            let threshold = router.threshold().unwrap_or(self.min_score);
+            let mut any_accepted = false;
+            for decision in decisions {
+                let accept = decision.score >= threshold;
+                any_accepted |= accept;
+                considered.push((decision, accept));
+            }
+            if any_accepted && !self.best_of {
+                break;
+            }
+        }
+
+        let chosen = considered
+            .iter()
+            .filter(|(_, accepted)| *accepted)
+            .map(|(d, _)| d)
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+            .cloned()
+            .unwrap_or_else(|| RoutingDecision {
+                tool: self.fallback_tool.clone(),
+                action: "execute".to_string(),
+                score: 0.0,
+                router: "fallback".to_string(),
+                reason: "no router decision met its threshold".to_string(),
+            });
+
+        // Best accepted decision per tool, highest score first.
+        let mut fan_out: Vec<RoutingDecision> = Vec::new();
+        if self.max_fan_out > 1 {
+            let mut accepted: Vec<&RoutingDecision> = considered.iter().filter(|(_, a)| *a).map(|(d, _)| d).collect();
+            accepted.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+            for decision in accepted {
+                if !fan_out.iter().any(|d| d.tool == decision.tool) {
+                    fan_out.push(decision.clone());
+                }
+            }
+            fan_out.truncate(self.max_fan_out);
+            if fan_out.len() < 2 {
+                fan_out.clear();
+            }
+        }
+
+        RoutingOutcome {
+            decision: chosen,
+            candidates: considered.into_iter().map(|(d, _)| d).collect(),
+            fan_out,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::embedder::Embedder;
+use crate::memory_service::AgentConfig;
+use crate::router::{build_router, CompositeRouter, Router};
+use serde::{Deserialize, Serialize};
+use shared_types::AppConfig;
+use std::fs;
+use std::sync::Arc;
+
+// Router Evaluation: scores each configured router, and their composition,
+// against messages labeled with the agent that should handle them.
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LabeledMessage {
+    pub message: String,
+    pub expected_tool: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Misroute {
+    pub message: String,
+    pub expected: String,
+    pub got: String,
+    pub score: f32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RouterEvalReport {
+    pub router: String,
+    pub total: usize,
+    pub correct: usize,
+    pub accuracy: f32,
+    pub misroutes: Vec<Misroute>,
+}
+
+// Each file may hold a single labeled object or an array of them, like
+// data/routing_eval.json.
+pub fn load_cases(paths: &[String]) -> Result<Vec<LabeledMessage>, String> {
+    let mut cases = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        let items = match value {
+            serde_json::Value::Array(items) => items,
+            single => vec![single],
+        };
+        for item in items {
+            let case: LabeledMessage = serde_json::from_value(item)
+                .map_err(|e| format!("{}: every case needs 'message' and 'expected_tool' ({})", path, e))?;
+            cases.push(case);
+        }
+    }
+    Ok(cases)
+}
+
+async fn evaluate_one(name: &str, router: &CompositeRouter, cases: &[LabeledMessage], agents: &[AgentConfig]) -> RouterEvalReport {
+    let mut misroutes = Vec::new();
+    for case in cases {
+        let decision = router.decide(&case.message, agents).await.decision;
+        let (tool, score) = (decision.tool, decision.score);
+        if tool != case.expected_tool {
+            misroutes.push(Misroute {
+                message: case.message.clone(),
+                expected: case.expected_tool.clone(),
+                got: tool,
+                score,
+            });
+        }
+    }
+
+    let correct = cases.len() - misroutes.len();
+    RouterEvalReport {
+        router: name.to_string(),
+        total: cases.len(),
+        correct,
+        accuracy: if cases.is_empty() { 0.0 } else { correct as f32 / cases.len() as f32 },
+        misroutes,
+    }
+}
+
+// One report per router in `[routing].strategy` (each used alone, with the
+// same threshold and fallback), followed by the composed router.
+pub async fn evaluate(
+    cases: &[LabeledMessage],
+    agents: &[AgentConfig],
+    app_config: &Arc<AppConfig>,
+    embedder: &Arc<dyn Embedder>,
+) -> Result<Vec<RouterEvalReport>, String> {
+    let mut reports = Vec::new();
+
+    for name in &app_config.routing.strategy {
+        let routers: Vec<Box<dyn Router>> = vec![build_router(name, app_config, embedder)?];
+        let single = CompositeRouter::new(routers, &app_config.routing);
+        reports.push(evaluate_one(name, &single, cases, agents).await);
+    }
+
+    let composite = CompositeRouter::from_config(app_config, embedder)?;
+    let label = format!("composite({})", app_config.routing.strategy.join(","));
+    reports.push(evaluate_one(&label, &composite, cases, agents).await);
+
+    Ok(reports)
+}
@@ -0,0 +1,215 @@
+use crate::cron::CronSchedule;
+use crate::memory_service::{MemoryService, TraceMeta};
+use crate::planner;
+use crate::self_correction::failed_response;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shared_types::{ActionRequest, ActionResponse, ActionSpec, AppConfig, Payload};
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Scheduler: runs stored tasks on cron schedules, either as a chat message
+// through the planner or as an explicit ActionRequest to one agent.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    // Drop missed runs and wait for the next occurrence.
+    Skip,
+    // Run once for any number of missed occurrences.
+    #[default]
+    RunOnce,
+    // Replay every missed occurrence (bounded by `max_catch_up_runs`).
+    RunAll,
+}
+
+impl CatchUpPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatchUpPolicy::Skip => "skip",
+            CatchUpPolicy::RunOnce => "run_once",
+            CatchUpPolicy::RunAll => "run_all",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "skip" => Some(CatchUpPolicy::Skip),
+            "run_once" => Some(CatchUpPolicy::RunOnce),
+            "run_all" => Some(CatchUpPolicy::RunAll),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledTask {
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    pub cron: String,
+    // Exactly one of `message` and `action` must be set.
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub action: Option<ActionSpec>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub last_run_at: Option<String>,
+    #[serde(default)]
+    pub next_run_at: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+pub fn to_timestamp(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn from_timestamp(s: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(s).ok().map(|t| t.with_timezone(&Local))
+}
+
+// Checks the task and fills in `next_run_at` from its cron expression.
+pub fn prepare_task(task: &mut ScheduledTask) -> Result<(), String> {
+    if task.name.trim().is_empty() {
+        return Err("Task name must not be empty".to_string());
+    }
+    if task.message.is_some() == task.action.is_some() {
+        return Err("Task must have exactly one of 'message' or 'action'".to_string());
+    }
+    let schedule = CronSchedule::parse(&task.cron)?;
+    task.next_run_at = schedule.next_after(Local::now()).map(to_timestamp);
+    Ok(())
+}
+
+pub async fn run_task(
+    task: &ScheduledTask,
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+) -> Result<ActionResponse, String> {
+    let meta = TraceMeta {
+        schedule_id: Some(task.id),
+        session_id: task.session_id.clone(),
+        ..Default::default()
+    };
+
+    let before = memory_service.count_schedule_traces(task.id).await.ok();
+    let result = if let Some(action) = &task.action {
+        planner::execute_action(action, memory_service.clone(), meta.clone()).await
+    } else {
+        let message = task.message.clone().unwrap_or_default();
+        planner::plan_and_execute(message, task.session_id.clone(), None, false, memory_service.clone(), app_config, meta.clone()).await
+    };
+
+    // Agent calls trace themselves. Runs that never reach one (memory
+    // commands, routing or registry errors) are traced here so they still
+    // show up in the schedule's run history.
+    let after = memory_service.count_schedule_traces(task.id).await.ok();
+    if before.is_some() && before == after {
+        let request = ActionRequest {
+            request_id: result.as_ref().map(|r| r.request_id).unwrap_or_else(|_| Uuid::new_v4()),
+            tool: task.action.as_ref().map(|a| a.tool.clone()).unwrap_or_else(|| "scheduler".to_string()),
+            action: task.action.as_ref().map(|a| a.action.clone()).unwrap_or_else(|| "message".to_string()),
+            context: task.action.as_ref().map(|a| a.context.clone()).unwrap_or_default(),
+            payload: Payload(match &task.action {
+                Some(action) => action.payload.clone(),
+                None => json!({ "prompt": task.message }),
+            }),
+        };
+        let failed;
+        let response = match &result {
+            Ok(response) => response,
+            Err(e) => {
+                failed = failed_response(&request, e.clone());
+                &failed
+            }
+        };
+        if let Err(e) = memory_service.log_action_trace(&request, response, &meta).await {
+            eprintln!("Failed to log action trace: {}", e);
+        }
+    }
+    result
+}
+
+// Runs every task whose `next_run_at` has passed, applying the catch-up
+// policy when the scheduler was not running at the scheduled time.
+pub async fn run_due_tasks(memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    let now = Local::now();
+    let due = match memory_service.list_scheduled_tasks(Some(to_timestamp(now))).await {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            eprintln!("Scheduler failed to load tasks: {}", e);
+            return;
+        }
+    };
+
+    for task in due {
+        let schedule = match CronSchedule::parse(&task.cron) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                eprintln!("Scheduled task '{}' has an invalid cron expression: {}", task.name, e);
+                continue;
+            }
+        };
+        let Some(first_due) = task.next_run_at.as_deref().and_then(from_timestamp) else {
+            continue;
+        };
+
+        let mut missed = 1;
+        let mut cursor = first_due;
+        while let Some(next) = schedule.next_after(cursor) {
+            if next > now || missed >= app_config.scheduler.max_catch_up_runs {
+                break;
+            }
+            missed += 1;
+            cursor = next;
+        }
+
+        let on_time = missed == 1 && (now - first_due).num_seconds() < app_config.scheduler.grace_seconds;
+        let runs = if on_time {
+            1
+        } else {
+            match task.catch_up {
+                CatchUpPolicy::Skip => 0,
+                CatchUpPolicy::RunOnce => 1,
+                CatchUpPolicy::RunAll => missed,
+            }
+        };
+
+        for _ in 0..runs {
+            println!("Running scheduled task '{}'", task.name);
+            if let Err(e) = run_task(&task, memory_service.clone(), app_config.clone()).await {
+                eprintln!("Scheduled task '{}' failed: {}", task.name, e);
+            }
+        }
+
+        let last_run_at = if runs > 0 { Some(to_timestamp(Local::now())) } else { None };
+        let next_run_at = schedule.next_after(now).map(to_timestamp);
+        if let Err(e) = memory_service.record_schedule_run(task.id, last_run_at, next_run_at).await {
+            eprintln!("Failed to record run for scheduled task '{}': {}", task.name, e);
+        }
+    }
+}
+
+pub fn spawn_scheduler(memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    if !app_config.scheduler.enabled {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(app_config.scheduler.tick_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            run_due_tasks(memory_service.clone(), app_config.clone()).await;
+        }
+    });
+}
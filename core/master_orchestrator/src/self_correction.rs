@@ -0,0 +1,206 @@
+use crate::executor::{call_llm_agent, execute_agent, extract_json_span, provider_config_json};
+use crate::memory_service::{MemoryService, TraceMeta};
+use crate::planner::resolve_provider;
+use serde::Deserialize;
+use serde_json::json;
+use shared_types::{ActionRequest, ActionResponse, AppConfig, Payload};
+use std::time::Instant;
+use uuid::Uuid;
+
+// Self-Correction: when an agent returns an error, ask the LLM to adjust the
+// request (arguments or tool) and retry. Every attempt is logged as its own
+// trace linked to the previous one through `parent_trace_id`.
+
+#[derive(Deserialize, Debug)]
+struct Replan {
+    #[serde(default)]
+    give_up: bool,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    tool: String,
+    #[serde(default)]
+    action: String,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+pub fn is_failure(response: &ActionResponse) -> bool {
+    response.status != "success"
+        || response.error.is_some()
+        || response.result.as_ref().is_some_and(|r| r.output_type == "error")
+}
+
+// Stands in for the response of an agent that could not be run at all.
+pub fn failed_response(request: &ActionRequest, error: String) -> ActionResponse {
+    ActionResponse {
+        request_id: request.request_id,
+        status: "error".to_string(),
+        code: 1,
+        result: None,
+        error: Some(error),
+    }
+}
+
+fn error_text(response: &ActionResponse) -> String {
+    response
+        .error
+        .clone()
+        .or_else(|| response.result.as_ref().map(|r| r.data.clone()))
+        .unwrap_or_else(|| format!("status {}", response.status))
+}
+
+// Strips provider credentials before showing a payload to the LLM.
+fn redacted_payload(request: &ActionRequest) -> serde_json::Value {
+    let mut payload = request.payload.0.clone();
+    if let Some(obj) = payload.as_object_mut() {
+        obj.remove("config");
+    }
+    payload
+}
+
+async fn replan(
+    user_message: &str,
+    request: &ActionRequest,
+    error: &str,
+    memory_service: &MemoryService,
+    app_config: &AppConfig,
+) -> Result<Replan, String> {
+    let agents = memory_service.get_active_agents().await?;
+    let agent_list = agents
+        .iter()
+        .map(|a| format!("- {}: {}", a.tool_name, a.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        "An agent call failed while handling the user's request.\n\n\
+         User request: {}\n\
+         Failed call: tool={} action={} payload={}\n\
+         Error: {}\n\n\
+         Available agents:\n{}\n\n\
+         Propose a corrected call. You may change the arguments, the action or the agent. \
+         Respond with only a JSON object: {{\"tool\": ..., \"action\": ..., \"payload\": {{...}}, \"reason\": ...}}, \
+         or {{\"give_up\": true, \"reason\": ...}} if retrying cannot help.",
+        user_message,
+        request.tool,
+        request.action,
+        redacted_payload(request),
+        error,
+        agent_list
+    );
+
+    let data = call_llm_agent(app_config, "replan", &prompt)?;
+    let json = extract_json_span(&data, '{', '}').ok_or("Re-planner did not return JSON")?;
+    let replan: Replan = serde_json::from_str(json).map_err(|e| format!("Invalid re-plan: {}", e))?;
+
+    if !replan.give_up && !agents.iter().any(|a| a.tool_name == replan.tool) {
+        return Err(format!("Re-planner chose unknown agent '{}'", replan.tool));
+    }
+    Ok(replan)
+}
+
+// How many times a failed call may be retried; what the estimator budgets
+// for.
+pub fn retries(app_config: &AppConfig) -> usize {
+    if app_config.self_correction.enabled {
+        app_config.self_correction.max_retries
+    } else {
+        0
+    }
+}
+
+// Retries a failed request up to `max_retries` times. Returns the last
+// request/response pair; its result metadata lists every attempt.
+pub async fn correct(
+    user_message: &str,
+    mut request: ActionRequest,
+    mut response: ActionResponse,
+    memory_service: &MemoryService,
+    app_config: &AppConfig,
+    base_meta: &TraceMeta,
+) -> (ActionRequest, ActionResponse) {
+    let mut attempts = vec![json!({
+        "attempt": 1,
+        "trace_id": request.request_id.to_string(),
+        "tool": request.tool,
+        "action": request.action,
+        "error": error_text(&response),
+    })];
+    let mut stop_reason = String::new();
+
+    for attempt in 2..=app_config.self_correction.max_retries + 1 {
+        if !is_failure(&response) {
+            break;
+        }
+
+        let replan = match replan(user_message, &request, &error_text(&response), memory_service, app_config).await {
+            Ok(replan) if replan.give_up => {
+                stop_reason = format!("Re-planner gave up: {}", replan.reason);
+                break;
+            }
+            Ok(replan) => replan,
+            Err(e) => {
+                stop_reason = e;
+                break;
+            }
+        };
+
+        // The LLM never sees credentials, so add them back for the agent it
+        // chose: the failed call's own settings when it keeps the agent,
+        // else the ones that agent would get from routing.
+        let mut payload = replan.payload;
+        let config = match request.payload.0.get("config") {
+            Some(config) if replan.tool == request.tool => Some(config.clone()),
+            _ => resolve_provider(&replan.tool, app_config).map(|(name, config)| provider_config_json(&name, &config)),
+        };
+        if let (Some(obj), Some(config)) = (payload.as_object_mut(), config) {
+            obj.insert("config".to_string(), config);
+        }
+
+        let retry = ActionRequest {
+            request_id: Uuid::new_v4(),
+            tool: replan.tool.clone(),
+            action: if replan.action.is_empty() { request.action.clone() } else { replan.action },
+            context: request.context.clone(),
+            payload: Payload(payload),
+        };
+
+        let started = Instant::now();
+        // An agent that could not run is one more failed attempt
+        let retry_response = execute_agent(&retry.tool, &retry).unwrap_or_else(|e| failed_response(&retry, e));
+
+        let meta = TraceMeta {
+            parent_trace_id: Some(request.request_id.to_string()),
+            attempt: Some(attempt as i64),
+            latency_ms: Some(started.elapsed().as_millis() as i64),
+            ..base_meta.clone()
+        };
+        if let Err(e) = memory_service.log_action_trace(&retry, &retry_response, &meta).await {
+            eprintln!("Failed to log action trace: {}", e);
+        }
+
+        attempts.push(json!({
+            "attempt": attempt,
+            "trace_id": retry.request_id.to_string(),
+            "parent_trace_id": request.request_id.to_string(),
+            "tool": retry.tool,
+            "action": retry.action,
+            "reason": replan.reason,
+            "error": if is_failure(&retry_response) { Some(error_text(&retry_response)) } else { None },
+        }));
+
+        request = retry;
+        response = retry_response;
+    }
+
+    if let Some(result) = response.result.as_mut() {
+        let mut metadata = result.metadata.take().unwrap_or_else(|| json!({}));
+        if let Some(obj) = metadata.as_object_mut() {
+            obj.insert("self_correction".to_string(), json!({"attempts": attempts, "stop_reason": stop_reason}));
+        }
+        result.metadata = Some(metadata);
+    }
+
+    (request, response)
+}
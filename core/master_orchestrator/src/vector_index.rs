@@ -0,0 +1,397 @@
+use crate::embedding::fnv1a64;
+use crate::quantize::{Code, Quantization, Query};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// In-memory approximate nearest-neighbour index (HNSW) over the semantic
+// memory vectors, so a query no longer opens every `.bin` file. Pure Rust,
+// no dependencies: the index is persisted as one JSON file and can always
+// be rebuilt from the vectors it was built from. Node vectors may be
+// quantized (see quantize.rs); similarities are then approximate.
+
+const FORMAT_VERSION: u32 = 1;
+// Max links per node on upper layers; layer 0 gets twice as many.
+const M: usize = 16;
+const EF_CONSTRUCTION: usize = 100;
+const EF_SEARCH: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Node {
+    id: String,
+    vector: Code,
+    // neighbors[layer] = indexes into `nodes`
+    neighbors: Vec<Vec<u32>>,
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct VectorIndex {
+    version: u32,
+    // Embedder the vectors came from, e.g. "simple-hash@v2/128"
+    #[serde(default)]
+    embedder: String,
+    dim: usize,
+    #[serde(default)]
+    quantization: Quantization,
+    nodes: Vec<Node>,
+    entry: Option<u32>,
+    #[serde(skip)]
+    positions: HashMap<String, u32>,
+}
+
+// Heap entry ordered by similarity.
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    sim: f32,
+    node: u32,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sim.total_cmp(&other.sim).then(self.node.cmp(&other.node))
+    }
+}
+
+// Deterministic level from the id, so rebuilds produce the same graph.
+fn random_level(id: &str) -> usize {
+    let uniform = (fnv1a64(id.as_bytes()) >> 11) as f64 / (1u64 << 53) as f64;
+    let ml = 1.0 / (M as f64).ln();
+    (-(uniform.max(f64::MIN_POSITIVE)).ln() * ml).floor() as usize
+}
+
+impl VectorIndex {
+    pub fn new(embedder: &str, dim: usize, quantization: Quantization) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            embedder: embedder.to_string(),
+            dim,
+            quantization,
+            ..Self::default()
+        }
+    }
+
+    pub fn build(
+        embedder: &str,
+        dim: usize,
+        quantization: Quantization,
+        items: impl IntoIterator<Item = (String, Vec<f32>)>,
+    ) -> Self {
+        let mut index = Self::new(embedder, dim, quantization);
+        for (id, vector) in items {
+            index.insert(&id, vector);
+        }
+        index
+    }
+
+    // A fresh graph over the live entries, dropping tombstones. Quantized
+    // vectors are rebuilt from their decoded values, which encode to the
+    // same codes.
+    pub fn compacted(&self) -> Self {
+        let items: Vec<_> = self.live_items().collect();
+        Self::build(&self.embedder, self.dim, self.quantization, items)
+    }
+
+    // Number of live (not deleted) entries.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn ids(&self) -> HashSet<String> {
+        self.positions.keys().cloned().collect()
+    }
+
+    // Bytes of vector data held for the live entries.
+    pub fn vector_bytes(&self) -> usize {
+        self.nodes.iter().filter(|n| !n.deleted).map(|n| n.vector.size()).sum()
+    }
+
+    // Fraction of nodes that are tombstones; a high value means it's time
+    // to rebuild.
+    pub fn deleted_ratio(&self) -> f32 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        1.0 - self.positions.len() as f32 / self.nodes.len() as f32
+    }
+
+    pub fn live_items(&self) -> impl Iterator<Item = (String, Vec<f32>)> + '_ {
+        self.nodes.iter().filter(|n| !n.deleted).map(|n| (n.id.clone(), n.vector.decode(self.dim)))
+    }
+
+    fn max_links(layer: usize) -> usize {
+        if layer == 0 {
+            M * 2
+        } else {
+            M
+        }
+    }
+
+    fn top_layer(&self) -> usize {
+        self.entry.map(|e| self.nodes[e as usize].neighbors.len() - 1).unwrap_or(0)
+    }
+
+    // Greedy best-first search of one layer; returns up to `ef` candidates,
+    // best first.
+    fn search_layer(&self, query: &Query, entry_points: &[u32], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entry_points.iter().copied().collect();
+        let mut candidates: BinaryHeap<Candidate> = BinaryHeap::new();
+        // Min-heap of the current best `ef`, via Reverse.
+        let mut found: BinaryHeap<std::cmp::Reverse<Candidate>> = BinaryHeap::new();
+
+        for &node in entry_points {
+            let c = Candidate {
+                sim: query.similarity(&self.nodes[node as usize].vector),
+                node,
+            };
+            candidates.push(c);
+            found.push(std::cmp::Reverse(c));
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = found.peek().map(|r| r.0.sim).unwrap_or(f32::MIN);
+            if current.sim < worst && found.len() >= ef {
+                break;
+            }
+            let Some(links) = self.nodes[current.node as usize].neighbors.get(layer) else {
+                continue;
+            };
+            for &next in links {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let c = Candidate {
+                    sim: query.similarity(&self.nodes[next as usize].vector),
+                    node: next,
+                };
+                let worst = found.peek().map(|r| r.0.sim).unwrap_or(f32::MIN);
+                if found.len() < ef || c.sim > worst {
+                    candidates.push(c);
+                    found.push(std::cmp::Reverse(c));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        let mut result: Vec<Candidate> = found.into_iter().map(|r| r.0).collect();
+        result.sort_by(|a, b| b.cmp(a));
+        result
+    }
+
+    // Keeps a node's links on one layer within the limit, dropping the least
+    // similar.
+    fn prune(&mut self, node: u32, layer: usize) {
+        let limit = Self::max_links(layer);
+        let links = &self.nodes[node as usize].neighbors[layer];
+        if links.len() <= limit {
+            return;
+        }
+        let base = self.nodes[node as usize].vector.decode(self.dim);
+        let base = Query::new(&base, self.quantization);
+        let mut scored: Vec<Candidate> = links
+            .iter()
+            .map(|&n| Candidate {
+                sim: base.similarity(&self.nodes[n as usize].vector),
+                node: n,
+            })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        scored.truncate(limit);
+        self.nodes[node as usize].neighbors[layer] = scored.into_iter().map(|c| c.node).collect();
+    }
+
+    // Adds or replaces the vector for `id`.
+    pub fn insert(&mut self, id: &str, vector: Vec<f32>) {
+        if vector.len() != self.dim {
+            eprintln!("Vector index: skipping '{}' ({} dims, expected {})", id, vector.len(), self.dim);
+            return;
+        }
+        self.remove(id);
+
+        let level = random_level(id);
+        let node = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            id: id.to_string(),
+            vector: Code::encode(vector.clone(), self.quantization),
+            neighbors: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.positions.insert(id.to_string(), node);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+
+        // Links are chosen with the full-precision vector
+        let query = Query::new(&vector, self.quantization);
+        let top = self.top_layer();
+        let mut entry_points = vec![entry];
+
+        // Descend greedily through the layers above the new node's level.
+        for layer in (level + 1..=top).rev() {
+            entry_points = vec![self.search_layer(&query, &entry_points, 1, layer)[0].node];
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &entry_points, EF_CONSTRUCTION, layer);
+            let links: Vec<u32> = found.iter().take(Self::max_links(layer)).map(|c| c.node).collect();
+            for &neighbor in &links {
+                self.nodes[neighbor as usize].neighbors[layer].push(node);
+                self.prune(neighbor, layer);
+            }
+            self.nodes[node as usize].neighbors[layer] = links;
+            entry_points = found.into_iter().map(|c| c.node).collect();
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    // Tombstones the entry; it stays in the graph for navigation until the
+    // next rebuild.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.positions.remove(id) {
+            Some(node) => {
+                self.nodes[node as usize].deleted = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Top-k live entries by cosine similarity (estimated, if quantized).
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        if k == 0 || query.len() != self.dim {
+            return Vec::new();
+        }
+        let query = &Query::new(query, self.quantization);
+
+        let mut entry_points = vec![entry];
+        for layer in (1..=self.top_layer()).rev() {
+            entry_points = vec![self.search_layer(query, &entry_points, 1, layer)[0].node];
+        }
+        // Widen the beam to make up for tombstones.
+        let ef = (k + self.nodes.len() - self.positions.len()).max(EF_SEARCH);
+        self.search_layer(query, &entry_points, ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.node as usize].deleted)
+            .take(k)
+            .map(|c| (self.nodes[c.node as usize].id.clone(), c.sim))
+            .collect()
+    }
+
+    // Top-k live entries accepted by `allow`. The beam is widened until k
+    // are found or the whole graph has been searched, so a selective filter
+    // still finds its matches.
+    pub fn search_filtered(&self, query: &[f32], k: usize, allow: impl Fn(&str) -> bool) -> Vec<(String, f32)> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        if k == 0 || query.len() != self.dim {
+            return Vec::new();
+        }
+        let query = &Query::new(query, self.quantization);
+
+        let mut entry_points = vec![entry];
+        for layer in (1..=self.top_layer()).rev() {
+            entry_points = vec![self.search_layer(query, &entry_points, 1, layer)[0].node];
+        }
+        let mut ef = (k + self.nodes.len() - self.positions.len()).max(EF_SEARCH);
+        loop {
+            let found: Vec<(String, f32)> = self
+                .search_layer(query, &entry_points, ef, 0)
+                .into_iter()
+                .filter(|c| !self.nodes[c.node as usize].deleted && allow(&self.nodes[c.node as usize].id))
+                .take(k)
+                .map(|c| (self.nodes[c.node as usize].id.clone(), c.sim))
+                .collect();
+            if found.len() >= k || ef >= self.nodes.len() {
+                return found;
+            }
+            ef = (ef * 4).min(self.nodes.len());
+        }
+    }
+
+    // Written to a temporary file and renamed, so a crash never leaves a
+    // half-written index.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+
+    // Fails if the file is missing, unreadable, structurally invalid, or built
+    // from another embedder's vectors or with another quantization.
+    pub fn load(path: &str, embedder: &str, dim: usize, quantization: Quantization) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let mut index: Self = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+        if index.version != FORMAT_VERSION {
+            return Err(format!("unsupported index version {}", index.version));
+        }
+        if index.embedder != embedder {
+            return Err(format!("index was built by '{}', expected '{}'", index.embedder, embedder));
+        }
+        if index.dim != dim {
+            return Err(format!("index has {} dims, expected {}", index.dim, dim));
+        }
+        if index.quantization != quantization {
+            return Err(format!(
+                "index is quantized as '{}', expected '{}'",
+                index.quantization.as_str(),
+                quantization.as_str()
+            ));
+        }
+        index.validate()?;
+        index.positions = index
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.deleted)
+            .map(|(i, n)| (n.id.clone(), i as u32))
+            .collect();
+        Ok(index)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let count = self.nodes.len() as u32;
+        match self.entry {
+            Some(entry) if entry >= count => return Err("entry point out of range".to_string()),
+            None if count > 0 => return Err("missing entry point".to_string()),
+            _ => {}
+        }
+        for node in &self.nodes {
+            if !node.vector.fits(self.dim, self.quantization) || node.neighbors.is_empty() {
+                return Err(format!("malformed node '{}'", node.id));
+            }
+            let links_ok = node.neighbors.iter().enumerate().all(|(layer, links)| {
+                links.iter().all(|&n| n < count && self.nodes[n as usize].neighbors.len() > layer)
+            });
+            if !links_ok {
+                return Err(format!("node '{}' has invalid links", node.id));
+            }
+        }
+        Ok(())
+    }
+}
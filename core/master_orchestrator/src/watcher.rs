@@ -0,0 +1,229 @@
+use crate::memory_service::{MemoryMetadata, MemoryService, MemorySource, TraceMeta};
+use crate::planner;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde_json::Value;
+use shared_types::{AppConfig, WatcherConfig};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Filesystem Triggers: watches configured directories (inotify on Linux),
+// debounces bursts of changes and fires the trigger's message, action or
+// built-in handler through the same execution path as chat.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+struct Trigger {
+    config: WatcherConfig,
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    fired: VecDeque<Instant>,
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| format!("Invalid glob '{}': {}", pattern, e))?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+impl Trigger {
+    fn new(config: WatcherConfig) -> Result<Self, String> {
+        let root = Path::new(&config.path)
+            .canonicalize()
+            .map_err(|e| format!("Cannot watch '{}': {}", config.path, e))?;
+        let include = if config.include.is_empty() {
+            None
+        } else {
+            Some(build_globset(&config.include)?)
+        };
+        let exclude = build_globset(&config.exclude)?;
+
+        let targets = [config.message.is_some(), config.action.is_some(), config.builtin.is_some()];
+        if targets.iter().filter(|t| **t).count() != 1 {
+            return Err("Watcher needs exactly one of 'message', 'action' or 'builtin'".to_string());
+        }
+        if let Some(builtin) = &config.builtin {
+            if builtin != "index_file" {
+                return Err(format!("Unknown built-in handler '{}'", builtin));
+            }
+        }
+
+        Ok(Self {
+            config,
+            root,
+            include,
+            exclude,
+            fired: VecDeque::new(),
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        self.include.as_ref().is_none_or(|set| set.is_match(relative))
+    }
+
+    // Sliding one-minute window rate limit.
+    fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        while self.fired.front().is_some_and(|t| now.duration_since(*t) > RATE_WINDOW) {
+            self.fired.pop_front();
+        }
+        if self.fired.len() >= self.config.max_per_minute as usize {
+            return false;
+        }
+        self.fired.push_back(now);
+        true
+    }
+}
+
+fn substitute_path(value: &Value, path: &str) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.replace("{path}", path)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| substitute_path(v, path)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), substitute_path(v, path))).collect()),
+        other => other.clone(),
+    }
+}
+
+async fn fire(trigger: &Trigger, paths: &[PathBuf], memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    let config = &trigger.config;
+    let path_list = paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join("\n");
+    let meta = TraceMeta {
+        trigger_name: Some(config.name.clone()),
+        ..Default::default()
+    };
+    println!("Watcher '{}' fired for {} path(s)", config.name, paths.len());
+
+    let result = if let Some(message) = &config.message {
+        let message = message.replace("{path}", &path_list);
+        planner::plan_and_execute(message, None, None, false, memory_service, app_config, meta).await.map(|_| ())
+    } else if let Some(action) = &config.action {
+        let mut action = action.clone();
+        action.context = action.context.replace("{path}", &path_list);
+        action.payload = substitute_path(&action.payload, &path_list);
+        planner::execute_action(&action, memory_service, meta).await.map(|_| ())
+    } else {
+        index_files(paths, &config.name, &memory_service).await
+    };
+
+    if let Err(e) = result {
+        eprintln!("Watcher '{}' failed: {}", config.name, e);
+    }
+}
+
+// Built-in "index_file": store each changed file's text in semantic memory.
+async fn index_files(paths: &[PathBuf], watcher: &str, memory_service: &MemoryService) -> Result<(), String> {
+    for path in paths {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue; // Deleted, or not text.
+        };
+        if content.trim().is_empty() {
+            continue;
+        }
+        let metadata = MemoryMetadata {
+            source: MemorySource::File,
+            tool: None,
+            session_id: None,
+            tags: vec![watcher.to_string()],
+        };
+        memory_service
+            .store_semantic_memory(&format!("File {}:\n{}", path.display(), content), &metadata)
+            .await?;
+    }
+    Ok(())
+}
+
+fn is_relevant(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+}
+
+fn spawn_watcher(mut trigger: Trigger, memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) -> Result<(), String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })
+    .map_err(|e| e.to_string())?;
+
+    let mode = if trigger.config.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(&trigger.root, mode).map_err(|e| e.to_string())?;
+    println!("Watching {} for trigger '{}'", trigger.root.display(), trigger.config.name);
+
+    tokio::spawn(async move {
+        // The watcher stops when dropped; keep it alive with the task.
+        let _watcher = watcher;
+        let debounce = Duration::from_millis(trigger.config.debounce_ms);
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => {
+                    let Some(event) = event else { break };
+                    if !is_relevant(&event.kind) {
+                        continue;
+                    }
+                    for path in event.paths {
+                        if trigger.matches(&path) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+                _ = poll.tick() => {
+                    let now = Instant::now();
+                    let mut ready: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, last)| now.duration_since(**last) >= debounce)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    if ready.is_empty() {
+                        continue;
+                    }
+                    ready.sort();
+                    for path in &ready {
+                        pending.remove(path);
+                    }
+
+                    let batches: Vec<Vec<PathBuf>> = if trigger.config.batch {
+                        vec![ready]
+                    } else {
+                        ready.into_iter().map(|p| vec![p]).collect()
+                    };
+                    for batch in batches {
+                        if trigger.allow() {
+                            fire(&trigger, &batch, memory_service.clone(), app_config.clone()).await;
+                        } else {
+                            eprintln!("Watcher '{}' rate limited; dropped {} path(s)", trigger.config.name, batch.len());
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+pub fn spawn_watchers(memory_service: Arc<MemoryService>, app_config: Arc<AppConfig>) {
+    for config in app_config.watchers.clone() {
+        let name = config.name.clone();
+        let result = Trigger::new(config).and_then(|t| spawn_watcher(t, memory_service.clone(), app_config.clone()));
+        if let Err(e) = result {
+            eprintln!("Failed to start watcher '{}': {}", name, e);
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use crate::estimator::{self, PlanEstimate, PlannedCall};
+use crate::memory_service::{MemoryService, TraceMeta};
+use crate::planner;
+use crate::scheduler::to_timestamp;
+use crate::self_correction::is_failure;
+use chrono::Local;
+use minijinja::Environment;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared_types::{ActionResponse, ActionResult, AppConfig, WorkflowConfig, WorkflowStep};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+// Workflows: named multi-step runs declared under [workflows.<name>] in
+// config. Each step is a chat message or an explicit action; steps can be
+// conditional on earlier outputs, and every agent call is traced with the
+// run id and step name.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepRecord {
+    pub name: String,
+    // "success", "error" or "skipped"
+    pub status: String,
+    pub output: String,
+    pub error: Option<String>,
+    pub attempts: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowRun {
+    pub run_id: String,
+    pub workflow: String,
+    pub inputs: BTreeMap<String, String>,
+    // "running", "success", "partial" (continued past errors) or "failed"
+    pub status: String,
+    pub steps: Vec<StepRecord>,
+    pub output: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowInvocation {
+    pub name: String,
+    pub inputs: HashMap<String, String>,
+}
+
+// `/workflow <name> key=value key2="quoted value"`
+pub fn parse_command(message: &str) -> Option<WorkflowInvocation> {
+    static ARG: OnceLock<Regex> = OnceLock::new();
+    let arg = ARG.get_or_init(|| Regex::new(r#"(\w+)=(?:"([^"]*)"|(\S+))"#).unwrap());
+
+    let rest = message.trim().strip_prefix("/workflow")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if name.is_empty() {
+        return None;
+    }
+
+    let inputs = arg
+        .captures_iter(args)
+        .map(|c| {
+            let value = c.get(2).or_else(|| c.get(3)).map(|m| m.as_str()).unwrap_or_default();
+            (c[1].to_string(), value.to_string())
+        })
+        .collect();
+    Some(WorkflowInvocation {
+        name: name.to_string(),
+        inputs,
+    })
+}
+
+pub fn validate(name: &str, workflow: &WorkflowConfig) -> Result<(), String> {
+    if workflow.steps.is_empty() {
+        return Err(format!("Workflow '{}' has no steps", name));
+    }
+    let policies = ["stop", "continue"];
+    if !policies.contains(&workflow.on_error.as_str()) {
+        return Err(format!("Workflow '{}': on_error must be 'stop' or 'continue'", name));
+    }
+    let mut seen = Vec::new();
+    for step in &workflow.steps {
+        if seen.contains(&step.name.as_str()) {
+            return Err(format!("Workflow '{}': duplicate step '{}'", name, step.name));
+        }
+        seen.push(step.name.as_str());
+        if step.message.is_some() == step.action.is_some() {
+            return Err(format!("Workflow '{}': step '{}' needs exactly one of 'message' or 'action'", name, step.name));
+        }
+        if step.on_error.as_deref().is_some_and(|p| !policies.contains(&p)) {
+            return Err(format!("Workflow '{}': step '{}' on_error must be 'stop' or 'continue'", name, step.name));
+        }
+        if let Some(condition) = &step.when {
+            Environment::new()
+                .compile_expression(condition)
+                .map_err(|e| format!("Workflow '{}': step '{}' has an invalid condition: {}", name, step.name, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn resolve_inputs(workflow: &WorkflowConfig, mut given: HashMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    let mut inputs = BTreeMap::new();
+    for input in &workflow.inputs {
+        match given.remove(&input.name).or_else(|| input.default.clone()) {
+            Some(value) => {
+                inputs.insert(input.name.clone(), value);
+            }
+            None => return Err(format!("Missing required input '{}'", input.name)),
+        }
+    }
+    if let Some(unknown) = given.keys().next() {
+        return Err(format!("Unknown input '{}'", unknown));
+    }
+    Ok(inputs)
+}
+
+fn template_context(inputs: &BTreeMap<String, String>, steps: &[StepRecord]) -> minijinja::Value {
+    let steps: BTreeMap<&str, &StepRecord> = steps.iter().map(|s| (s.name.as_str(), s)).collect();
+    minijinja::Value::from_serialize(json!({
+        "inputs": inputs,
+        "steps": steps,
+        "date": Local::now().format("%Y-%m-%d").to_string(),
+    }))
+}
+
+fn render_value(env: &Environment, value: &Value, ctx: &minijinja::Value) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(s) => Value::String(env.render_str(s, ctx.clone()).map_err(|e| e.to_string())?),
+        Value::Array(items) => Value::Array(items.iter().map(|v| render_value(env, v, ctx)).collect::<Result<_, _>>()?),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), render_value(env, v, ctx)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+fn response_text(response: &ActionResponse) -> String {
+    response.result.as_ref().map(|r| r.data.clone()).unwrap_or_default()
+}
+
+// One attempt at a step; Ok carries the agent's output.
+async fn execute_step(
+    step: &WorkflowStep,
+    env: &Environment<'_>,
+    ctx: &minijinja::Value,
+    memory_service: &Arc<MemoryService>,
+    app_config: &Arc<AppConfig>,
+    meta: &TraceMeta,
+) -> Result<String, String> {
+    let response = if let Some(message) = &step.message {
+        let message = env.render_str(message, ctx.clone()).map_err(|e| e.to_string())?;
+        // Boxed: plan_and_execute can itself start a workflow.
+        Box::pin(planner::plan_and_execute(message, None, None, false, memory_service.clone(), app_config.clone(), meta.clone())).await?
+    } else if let Some(action) = &step.action {
+        let mut action = action.clone();
+        action.context = env.render_str(&action.context, ctx.clone()).map_err(|e| e.to_string())?;
+        action.payload = render_value(env, &action.payload, ctx)?;
+        planner::execute_action(&action, memory_service.clone(), meta.clone()).await?
+    } else {
+        return Err("Step has nothing to run".to_string());
+    };
+
+    if is_failure(&response) {
+        let error = response.error.clone().unwrap_or_else(|| response_text(&response));
+        return Err(error);
+    }
+    Ok(response_text(&response))
+}
+
+// Every step is assumed to run and succeed; earlier outputs render as empty
+// strings and message steps are routed as chat would route them.
+pub async fn estimate(
+    name: &str,
+    inputs: HashMap<String, String>,
+    memory_service: &MemoryService,
+    app_config: &Arc<AppConfig>,
+) -> Result<PlanEstimate, String> {
+    let workflow = app_config.workflows.get(name).ok_or_else(|| format!("Unknown workflow: {}", name))?;
+    validate(name, workflow)?;
+    let inputs = resolve_inputs(workflow, inputs)?;
+    let placeholders: Vec<StepRecord> = workflow
+        .steps
+        .iter()
+        .map(|s| StepRecord {
+            name: s.name.clone(),
+            status: "success".to_string(),
+            output: String::new(),
+            error: None,
+            attempts: 1,
+        })
+        .collect();
+    let ctx = template_context(&inputs, &placeholders);
+    let env = Environment::new();
+    let agents = memory_service.get_active_agents().await.map_err(|e| format!("Memory Error: {}", e))?;
+
+    let mut calls = Vec::new();
+    for step in &workflow.steps {
+        if let Some(message) = &step.message {
+            let message = env.render_str(message, ctx.clone()).map_err(|e| e.to_string())?;
+            let decision = planner::route(&message, &agents, memory_service, app_config).await?.decision;
+            let payload = json!({ "prompt": message });
+            calls.push(PlannedCall::new(&decision.tool, &decision.action, "", &payload, app_config).with_retries(step.retries as usize));
+        } else if let Some(action) = &step.action {
+            let mut action = action.clone();
+            action.context = env.render_str(&action.context, ctx.clone()).map_err(|e| e.to_string())?;
+            action.payload = render_value(&env, &action.payload, &ctx)?;
+            calls.push(PlannedCall::from_action(&action, app_config).with_retries(step.retries as usize));
+        }
+    }
+    Ok(estimator::estimate_sequence(calls, memory_service, app_config).await)
+}
+
+pub async fn run(
+    name: &str,
+    inputs: HashMap<String, String>,
+    memory_service: Arc<MemoryService>,
+    app_config: Arc<AppConfig>,
+    base_meta: TraceMeta,
+) -> Result<WorkflowRun, String> {
+    if base_meta.workflow_run_id.is_some() {
+        return Err("Workflows cannot start other workflows".to_string());
+    }
+    let workflow = app_config
+        .workflows
+        .get(name)
+        .ok_or_else(|| format!("Unknown workflow: {}", name))?
+        .clone();
+    validate(name, &workflow)?;
+    if app_config.estimation.enabled {
+        let plan = estimate(name, inputs.clone(), &memory_service, &app_config).await?;
+        estimator::enforce(&plan, &app_config)?;
+    }
+
+    let mut run = WorkflowRun {
+        run_id: Uuid::new_v4().to_string(),
+        workflow: name.to_string(),
+        inputs: resolve_inputs(&workflow, inputs)?,
+        status: "running".to_string(),
+        steps: Vec::new(),
+        output: None,
+        started_at: to_timestamp(Local::now()),
+        finished_at: None,
+    };
+    memory_service.record_workflow_run(&run).await?;
+    println!("Workflow '{}' started (run {})", name, run.run_id);
+    // From here on every failure is recorded on the run, so it never stays
+    // "running".
+
+    let env = Environment::new();
+    let mut had_errors = false;
+    let mut stopped = false;
+
+    for step in &workflow.steps {
+        let ctx = template_context(&run.inputs, &run.steps);
+
+        if let Some(condition) = &step.when {
+            // validate() has compiled every condition already
+            let passed = match env.compile_expression(condition) {
+                Ok(expr) => expr.eval(ctx.clone()).map(|v| v.is_true()).unwrap_or(false),
+                Err(e) => {
+                    run.steps.push(StepRecord {
+                        name: step.name.clone(),
+                        status: "error".to_string(),
+                        output: String::new(),
+                        error: Some(format!("Invalid condition: {}", e)),
+                        attempts: 0,
+                    });
+                    had_errors = true;
+                    stopped = true;
+                    break;
+                }
+            };
+            if !passed {
+                run.steps.push(StepRecord {
+                    name: step.name.clone(),
+                    status: "skipped".to_string(),
+                    output: String::new(),
+                    error: None,
+                    attempts: 0,
+                });
+                continue;
+            }
+        }
+
+        let meta = TraceMeta {
+            workflow_run_id: Some(run.run_id.clone()),
+            workflow_step: Some(step.name.clone()),
+            ..base_meta.clone()
+        };
+
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            match execute_step(step, &env, &ctx, &memory_service, &app_config, &meta).await {
+                Err(e) if attempts <= step.retries => {
+                    eprintln!("Workflow '{}' step '{}' failed (attempt {}): {}", name, step.name, attempts, e);
+                }
+                result => break result,
+            }
+        };
+
+        let failed = result.is_err();
+        run.steps.push(match result {
+            Ok(output) => StepRecord {
+                name: step.name.clone(),
+                status: "success".to_string(),
+                output,
+                error: None,
+                attempts,
+            },
+            Err(e) => StepRecord {
+                name: step.name.clone(),
+                status: "error".to_string(),
+                output: String::new(),
+                error: Some(e),
+                attempts,
+            },
+        });
+        if let Err(e) = memory_service.record_workflow_run(&run).await {
+            eprintln!("Failed to record workflow run {}: {}", run.run_id, e);
+        }
+
+        if failed {
+            had_errors = true;
+            if step.on_error.as_deref().unwrap_or(&workflow.on_error) == "stop" {
+                stopped = true;
+                break;
+            }
+        }
+    }
+
+    // A stopped run has no meaningful output; render errors are recorded
+    // on the run rather than losing it.
+    run.output = match &workflow.output {
+        _ if stopped => None,
+        Some(template) => match env.render_str(template, template_context(&run.inputs, &run.steps)) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                had_errors = true;
+                Some(format!("Workflow output failed to render: {}", e))
+            }
+        },
+        None => run.steps.iter().rev().find(|s| s.status == "success").map(|s| s.output.clone()),
+    };
+    run.status = if stopped {
+        "failed"
+    } else if had_errors {
+        "partial"
+    } else {
+        "success"
+    }
+    .to_string();
+    run.finished_at = Some(to_timestamp(Local::now()));
+    memory_service.record_workflow_run(&run).await?;
+    println!("Workflow '{}' finished: {}", name, run.status);
+
+    Ok(run)
+}
+
+// Chat reply for a workflow run: its output, with the run in metadata.
+pub fn to_response(run: &WorkflowRun) -> ActionResponse {
+    let ok = run.status != "failed";
+    let data = match (&run.output, ok) {
+        (Some(output), true) => output.clone(),
+        _ => {
+            let failed = run.steps.iter().rev().find(|s| s.status == "error");
+            format!(
+                "Workflow '{}' {}{}",
+                run.workflow,
+                run.status,
+                failed
+                    .map(|s| format!(" at step '{}': {}", s.name, s.error.clone().unwrap_or_default()))
+                    .unwrap_or_default()
+            )
+        }
+    };
+
+    ActionResponse {
+        request_id: Uuid::parse_str(&run.run_id).unwrap_or_else(|_| Uuid::new_v4()),
+        status: if ok { "success" } else { "error" }.to_string(),
+        code: if ok { 0 } else { 1 },
+        result: Some(ActionResult {
+            output_type: if ok { "text" } else { "error" }.to_string(),
+            data,
+            metadata: Some(json!({ "workflow": run })),
+        }),
+        error: None,
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
 use uuid::Uuid;
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Payload(pub Value);
@@ -30,6 +31,18 @@ pub struct ActionResponse {
     pub error: Option<String>,
 }
 
+// An explicit agent call, used where the planner's intent detection is
+// bypassed (scheduled tasks, filesystem triggers).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionSpec {
+    pub tool: String,
+    pub action: String,
+    #[serde(default)]
+    pub context: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProviderConfig {
     pub api_key: Option<String>,
@@ -49,7 +62,613 @@ pub struct LLMConfig {
     pub lmstudio: Option<ProviderConfig>,
 }
 
+impl LLMConfig {
+    pub fn provider(&self, name: &str) -> Option<&ProviderConfig> {
+        match name {
+            "openrouter" => self.openrouter.as_ref(),
+            "gemini" => self.gemini.as_ref(),
+            "grok" => self.grok.as_ref(),
+            "openai" => self.openai.as_ref(),
+            "anthropic" => self.anthropic.as_ref(),
+            "ollama" => self.ollama.as_ref(),
+            "lmstudio" => self.lmstudio.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
     pub llm: LLMConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default)]
+    pub entities: EntityConfig,
+    #[serde(default)]
+    pub facts: FactExtractionConfig,
+    #[serde(default)]
+    pub prompts: PromptConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub watchers: Vec<WatcherConfig>,
+    #[serde(default)]
+    pub self_correction: SelfCorrectionConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub fan_out: FanOutConfig,
+    #[serde(default)]
+    pub workflows: HashMap<String, WorkflowConfig>,
+    #[serde(default)]
+    pub goals: GoalsConfig,
+    #[serde(default)]
+    pub estimation: EstimationConfig,
+    #[serde(default)]
+    pub embedding: EmbeddingConfig,
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    // Agent manifests, keyed by tool name.
+    #[serde(default)]
+    pub agents: HashMap<String, AgentManifest>,
+}
+
+// Context assembly settings. Budgets are looked up as "provider:model",
+// then "provider", then fall back to `default_budget_tokens`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub default_budget_tokens: usize,
+    pub budgets: HashMap<String, usize>,
+    // Overrides for the chars-per-token heuristic, keyed by model name prefix.
+    pub chars_per_token: HashMap<String, f32>,
+    pub max_kg_facts: usize,
+    pub max_semantic_memories: usize,
+    pub max_history_messages: usize,
+    pub kg_weight: f32,
+    pub semantic_weight: f32,
+    pub history_weight: f32,
+    pub max_goals: usize,
+    pub goals_weight: f32,
+    // Context providers to query, by name.
+    pub providers: Vec<String>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            default_budget_tokens: 1500,
+            budgets: HashMap::new(),
+            chars_per_token: HashMap::new(),
+            max_kg_facts: 20,
+            max_semantic_memories: 8,
+            max_history_messages: 10,
+            kg_weight: 1.0,
+            semantic_weight: 0.8,
+            history_weight: 0.9,
+            max_goals: 5,
+            goals_weight: 1.2,
+            providers: vec![
+                "goals".to_string(),
+                "knowledge_graph".to_string(),
+                "semantic".to_string(),
+                "session_history".to_string(),
+            ],
+        }
+    }
+}
+
+// Entity extraction for knowledge-graph lookups.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EntityConfig {
+    pub max_ngram: usize,
+    // Ask the LLM for entities in addition to n-gram matching.
+    pub llm_assisted: bool,
+    pub include_neighbours: bool,
+}
+
+impl Default for EntityConfig {
+    fn default() -> Self {
+        Self {
+            max_ngram: 4,
+            llm_assisted: false,
+            include_neighbours: true,
+        }
+    }
+}
+
+// Post-response extraction of knowledge-graph triples.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FactExtractionConfig {
+    pub enabled: bool,
+    // Use the LLM extractor; the rule-based one always runs.
+    pub llm_extraction: bool,
+    // Also extract from agent and LLM output, not just user messages.
+    pub extract_from_results: bool,
+    // When true, extracted facts stay out of retrieval until approved.
+    pub require_review: bool,
+    pub max_facts_per_text: usize,
+}
+
+impl Default for FactExtractionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            llm_extraction: false,
+            extract_from_results: false,
+            require_review: true,
+            max_facts_per_text: 10,
+        }
+    }
+}
+
+// Named prompt templates. Template bodies use minijinja syntax; write
+// variables with spaces (`{{ user_message }}`) so the config loader's
+// `{{ENV_VAR}}` substitution leaves them alone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PromptConfig {
+    pub default_template: String,
+    pub templates: HashMap<String, PromptTemplate>,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            default_template: "default".to_string(),
+            templates: HashMap::new(),
+        }
+    }
+}
+
+// Empty fields fall back to the built-in defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PromptTemplate {
+    pub persona: String,
+    pub system: String,
+    // Layout of the memory context section.
+    pub context: String,
+    pub user: String,
+    // Per-tool overrides of `user`, keyed by agent tool name.
+    pub tools: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub enabled: bool,
+    pub tick_seconds: u64,
+    // A run this late or later counts as missed and follows the task's catch-up policy.
+    pub grace_seconds: i64,
+    // Upper bound on runs replayed by the "run_all" catch-up policy.
+    pub max_catch_up_runs: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tick_seconds: 30,
+            grace_seconds: 120,
+            max_catch_up_runs: 24,
+        }
+    }
+}
+
+// A filesystem trigger. Exactly one of `message`, `action` or `builtin` is
+// expected; `{path}` in the message or payload strings is replaced by the
+// changed file (or a newline-separated list in batch mode).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatcherConfig {
+    pub name: String,
+    pub path: String,
+    #[serde(default = "default_true")]
+    pub recursive: bool,
+    // Glob patterns relative to `path`; empty means everything.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    #[serde(default = "default_max_per_minute")]
+    pub max_per_minute: u32,
+    // Fire once per debounced batch instead of once per changed file.
+    #[serde(default)]
+    pub batch: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub action: Option<ActionSpec>,
+    // Built-in handlers: "index_file" stores the file text in semantic memory.
+    #[serde(default)]
+    pub builtin: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_debounce_ms() -> u64 {
+    1500
+}
+
+fn default_max_per_minute() -> u32 {
+    30
+}
+
+// Re-planning after an agent returns an error.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SelfCorrectionConfig {
+    pub enabled: bool,
+    pub max_retries: usize,
+}
+
+impl Default for SelfCorrectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_retries: 2,
+        }
+    }
+}
+
+// Planner routing. Routers listed in `strategy` are consulted in order;
+// with mode "first" the first decision scoring at least `min_score` wins,
+// with mode "best" the highest-scoring decision wins. Below the threshold
+// the request goes to `fallback_tool`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub strategy: Vec<String>,
+    pub mode: String,
+    pub min_score: f32,
+    pub fallback_tool: String,
+    pub keywords: HashMap<String, Vec<String>>,
+    pub rules: Vec<RoutingRule>,
+    // Minimum cosine similarity for the "embedding" router; it overrides
+    // `min_score` for that router's decisions.
+    pub embedding_threshold: f32,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        let mut keywords = HashMap::new();
+        keywords.insert("git_agent".to_string(), vec!["git".to_string(), "commit".to_string()]);
+        Self {
+            strategy: vec!["rules".to_string(), "keyword".to_string()],
+            mode: "first".to_string(),
+            min_score: 0.5,
+            fallback_tool: "llm_router_agent".to_string(),
+            keywords,
+            rules: Vec::new(),
+            embedding_threshold: 0.55,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoutingRule {
+    // Regular expression matched against the user message.
+    pub pattern: String,
+    pub tool: String,
+    #[serde(default = "default_action")]
+    pub action: String,
+    #[serde(default = "default_rule_score")]
+    pub score: f32,
+}
+
+fn default_action() -> String {
+    "execute".to_string()
+}
+
+fn default_rule_score() -> f32 {
+    0.9
+}
+
+// Parallel dispatch when routing accepts several agents for one message.
+// `merge` is "concat", "synthesize" (LLM summary of all results) or
+// "first_success".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FanOutConfig {
+    pub enabled: bool,
+    pub merge: String,
+    pub timeout_ms: u64,
+    pub max_agents: usize,
+}
+
+impl Default for FanOutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            merge: "concat".to_string(),
+            timeout_ms: 20_000,
+            max_agents: 3,
+        }
+    }
+}
+
+// A named multi-step workflow. Step messages, payloads and conditions are
+// minijinja templates over `inputs` and prior `steps.<name>.{status,output}`.
+// `on_error` is "stop" or "continue"; `output` renders the final result
+// (default: the last successful step's output).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowConfig {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub inputs: Vec<WorkflowInput>,
+    pub steps: Vec<WorkflowStep>,
+    #[serde(default = "default_on_error")]
+    pub on_error: String,
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+// Inputs without a default are required.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowInput {
+    pub name: String,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+// Runs either a chat `message` (routed like chat) or an explicit `action`.
+// `when` is an expression; the step is skipped when it is falsy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorkflowStep {
+    pub name: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub action: Option<ActionSpec>,
+    #[serde(default)]
+    pub when: Option<String>,
+    // Overrides the workflow's `on_error` for this step.
+    #[serde(default)]
+    pub on_error: Option<String>,
+    #[serde(default)]
+    pub retries: u32,
+}
+
+fn default_on_error() -> String {
+    "stop".to_string()
+}
+
+// Long-running goals. Chat traces are linked to active goals whose text is
+// at least `link_threshold` similar to the message; check-ins run every
+// `check_in_hours` (0 disables them).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GoalsConfig {
+    pub auto_link: bool,
+    pub link_threshold: f32,
+    pub check_in_hours: u64,
+    pub max_check_in_traces: usize,
+}
+
+impl Default for GoalsConfig {
+    fn default() -> Self {
+        Self {
+            auto_link: true,
+            link_threshold: 0.3,
+            check_in_hours: 24,
+            max_check_in_traces: 20,
+        }
+    }
+}
+
+// Plan cost and latency estimation. Prices are per million tokens, keyed
+// by "provider:model" or model name. Latency comes from the last
+// `history_window` traces of each agent action, else `default_latency_ms`.
+// Ceilings apply to the worst case, with every retry used; a cost ceiling
+// refuses plans whose cost is unknown. Unset ceilings are not enforced.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EstimationConfig {
+    pub enabled: bool,
+    pub prices: HashMap<String, ModelPrice>,
+    pub history_window: usize,
+    pub default_latency_ms: u64,
+    pub default_output_tokens: usize,
+    pub max_tokens: Option<usize>,
+    pub max_cost_usd: Option<f64>,
+    pub max_latency_ms: Option<u64>,
+}
+
+impl Default for EstimationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            prices: HashMap::new(),
+            history_window: 50,
+            default_latency_ms: 2000,
+            default_output_tokens: 400,
+            max_tokens: None,
+            max_cost_usd: None,
+            max_latency_ms: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+// Semantic memory embedder: "hash" (built-in bag of words), "tfidf"
+// (feature-hashed word and character n-grams weighted by IDF) or "http"
+// (an OpenAI-compatible or Ollama embeddings endpoint of an [llm] provider).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmbeddingConfig {
+    pub embedder: String,
+    // tfidf: vector size. http: expected size; probed when unset.
+    pub dim: Option<usize>,
+    pub word_ngrams: usize,
+    // Character n-gram length within words; 0 disables them.
+    pub char_ngrams: usize,
+    pub provider: Option<String>,
+    // Defaults to the provider's model_name.
+    pub model: Option<String>,
+    // "openai" or "ollama"
+    pub api: String,
+    pub timeout_ms: u64,
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            embedder: "hash".to_string(),
+            dim: None,
+            word_ngrams: 2,
+            char_ngrams: 3,
+            provider: None,
+            model: None,
+            api: "openai".to_string(),
+            timeout_ms: 30000,
+            batch_size: 32,
+        }
+    }
+}
+
+// Semantic memory retrieval: "vector", "lexical" (FTS5 BM25) or "hybrid",
+// which merges both rankings by weighted reciprocal rank fusion:
+// score = sum(weight / (rrf_k + rank)), scaled so a top hit in every
+// ranking scores 1.0. That relevance is then weighted by recency and
+// importance: relevance * (1 - rw - iw + rw * recency + iw * importance),
+// where recency halves every `recency_half_life_days` since last seen.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetrievalConfig {
+    pub mode: String,
+    pub vector_weight: f32,
+    pub lexical_weight: f32,
+    pub rrf_k: f32,
+    // Results taken from each ranking before fusion.
+    pub candidates: usize,
+    // Hits below this raw score (cosine similarity or BM25) are dropped
+    // before fusion.
+    pub min_score: f32,
+    pub recency_weight: f32,
+    pub importance_weight: f32,
+    pub recency_half_life_days: f32,
+    // Return whole documents instead of their matching chunks.
+    pub return_parents: bool,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            mode: "hybrid".to_string(),
+            vector_weight: 1.0,
+            lexical_weight: 1.0,
+            rrf_k: 60.0,
+            candidates: 50,
+            min_score: 0.0,
+            recency_weight: 0.2,
+            importance_weight: 0.2,
+            recency_half_life_days: 30.0,
+            return_parents: false,
+        }
+    }
+}
+
+// Semantic memory upkeep. A new memory at least `dedup_threshold` similar
+// to one from the same source replaces its text and raises its hit count
+// instead of being stored again; a threshold above 1.0 disables merging.
+// Chat memories (`/remember`) are never merged.
+// `importance` is the starting importance per source (0..1). `ttl_days`
+// expires memories of a source once they haven't been seen for that long;
+// sources without an entry are kept forever.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MemoryConfig {
+    pub dedup_threshold: f32,
+    pub importance: HashMap<String, f32>,
+    pub ttl_days: HashMap<String, u64>,
+    pub expiry_interval_minutes: u64,
+    pub chunking: ChunkingConfig,
+    pub quantization: QuantizationConfig,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            dedup_threshold: 0.95,
+            importance: HashMap::from([
+                ("chat".to_string(), 0.9),
+                ("vault_note".to_string(), 0.7),
+                ("git_commit".to_string(), 0.6),
+                ("file".to_string(), 0.5),
+                ("trace".to_string(), 0.3),
+            ]),
+            ttl_days: HashMap::new(),
+            expiry_interval_minutes: 60,
+            chunking: ChunkingConfig::default(),
+            quantization: QuantizationConfig::default(),
+        }
+    }
+}
+
+// Texts longer than `max_chars` (in bytes) are stored as chunks: split at
+// Markdown headings and paragraphs, or by a sliding window overlapping by
+// `overlap_chars`. `return_parents` in [retrieval] picks whether retrieval
+// returns the matching chunks or their reassembled documents.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    pub enabled: bool,
+    pub max_chars: usize,
+    pub overlap_chars: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_chars: 1500,
+            overlap_chars: 200,
+        }
+    }
+}
+
+// How the in-memory index holds vectors: "none" (f32), "int8" (one byte
+// per dimension plus a scale) or "binary" (one sign bit per dimension).
+// Quantized searches fetch `oversample` times as many candidates and
+// re-score them with the full-precision vectors kept in the database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QuantizationConfig {
+    pub mode: String,
+    pub oversample: usize,
+}
+
+impl Default for QuantizationConfig {
+    fn default() -> Self {
+        Self {
+            mode: "none".to_string(),
+            oversample: 4,
+        }
+    }
+}
+
+// Describes what an agent handles. `description` overrides the registry
+// text; `examples` are typical requests, used by the embedding router.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AgentManifest {
+    pub description: Option<String>,
+    pub examples: Vec<String>,
 }
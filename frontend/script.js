@@ -1,5 +1,8 @@
 const API_ENDPOINT = 'http://127.0.0.1:8181/api/chat';
 
+// One session per page load; the orchestrator keeps history keyed by this id.
+const SESSION_ID = (crypto.randomUUID ? crypto.randomUUID() : 'session-' + Date.now());
+
 const chatContainer = document.getElementById('chat-container');
 const messageInput = document.getElementById('message-input');
 const sendBtn = document.getElementById('send-btn');
@@ -62,13 +65,16 @@ async function handleSend() {
         if (response.status === 'success') {
             // Parse the inner JSON string if it exists
             let output = response.output;
+            let sources = null;
             try {
                 const parsed = JSON.parse(output);
                 if (parsed.data) output = parsed.data;
+                sources = parsed.metadata && parsed.metadata.fan_out ? parsed.metadata.fan_out.sources : null;
             } catch (e) {
                 // Not JSON, use as is
             }
-            appendMessage('Agent', output, 'agent');
+            const msgId = appendMessage('Agent', output, 'agent');
+            if (sources) appendSources(msgId, sources);
         } else {
             appendMessage('Error', response.output || 'Unknown error', 'agent');
         }
@@ -107,6 +113,23 @@ function appendMessage(role, text, type, isLoading = false) {
     return id;
 }
 
+// Per-agent attribution for fanned-out requests.
+function appendSources(msgId, sources) {
+    const msgDiv = document.getElementById(msgId);
+    if (!msgDiv) return;
+
+    const list = document.createElement('div');
+    list.className = 'sources';
+    sources.forEach(source => {
+        const item = document.createElement('span');
+        item.className = `source ${source.status}`;
+        item.textContent = `${source.tool}: ${source.status}` + (source.latency_ms ? ` (${source.latency_ms} ms)` : '');
+        if (source.error) item.title = source.error;
+        list.appendChild(item);
+    });
+    msgDiv.appendChild(list);
+}
+
 function removeMessage(id) {
     const el = document.getElementById(id);
     if (el) el.remove();
@@ -129,7 +152,7 @@ async function sendMessage(message) {
             headers: {
                 'Content-Type': 'application/json',
             },
-            body: JSON.stringify({ message: message }),
+            body: JSON.stringify({ message: message, session_id: SESSION_ID }),
         });
         const data = await response.json();
         return data;